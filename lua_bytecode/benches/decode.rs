@@ -0,0 +1,25 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use lua_bytecode::decoder::luajit::DecodedLuaJitBytecode;
+
+const SAMPLE: &[u8] = include_bytes!("../examples/files/compiled_1");
+
+fn decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+    group.throughput(Throughput::Bytes(SAMPLE.len() as u64));
+
+    group.bench_with_input(
+        BenchmarkId::new("from_read", "compiled_1"),
+        SAMPLE,
+        |b, data| b.iter(|| DecodedLuaJitBytecode::from_read(data).unwrap()),
+    );
+    group.bench_with_input(
+        BenchmarkId::new("from_slice", "compiled_1"),
+        SAMPLE,
+        |b, data| b.iter(|| DecodedLuaJitBytecode::from_slice(data).unwrap()),
+    );
+
+    group.finish();
+}
+
+criterion_group!(benches, decode);
+criterion_main!(benches);
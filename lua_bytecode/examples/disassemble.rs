@@ -0,0 +1,22 @@
+use lua_bytecode::decoder::{error::Result, luajit::DecodedLuaJitBytecode};
+
+/// Prints the listing of the dump given on the command line, or of the sample dump
+///
+/// Instructions patched by the JIT compiler are turned back into the ones `luajit -bl` prints.
+fn main() -> Result<()> {
+    let raw_file = match std::env::args_os().nth(1) {
+        Some(path) => std::fs::read(path)?,
+        None => std::include_bytes!("./files/compiled_1").to_vec(),
+    };
+    let mut decoded = DecodedLuaJitBytecode::from_slice(&raw_file)?;
+
+    for instruction in decoded.normalise() {
+        eprintln!(
+            "left {} at pc {} of prototype {}",
+            instruction.opcode, instruction.pc, instruction.prototype
+        );
+    }
+
+    print!("{}", decoded.disassemble());
+    Ok(())
+}
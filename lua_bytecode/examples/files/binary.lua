@@ -0,0 +1,3 @@
+local caf = "\255\254 tab\t\0 quote\" caf\195\169"
+
+return caf
@@ -0,0 +1,22 @@
+local function sum(...)
+  local total = 0
+  for _, v in ipairs({...}) do
+    total = total + v
+  end
+  for k, v in pairs({a = 1}) do
+    total = total + v
+  end
+  local t = {}
+  for k, v in next, t do
+    total = total + v
+  end
+  return total
+end
+
+local function spread(...)
+  local a, b = ...
+  return sum(a, b, ...)
+end
+
+print(spread(1, 2, 3))
+return sum(select(2, 1, 2))
@@ -0,0 +1,55 @@
+-- BYTECODE -- calls.lua:1-14
+0001    KSHORT   0   0
+0002    GGET     1   0      ; "ipairs"
+0003    TNEW     2   3
+0004    VARG     3   0   0
+0005    TSETM    3   0      ; 1
+0006    CALL     1   4   2
+0007    JMP      4 => 0009
+0008 => ADDVV    0   0   5
+0009 => ITERC    4   3   3
+0010    ITERL    4 => 0008
+0011    GGET     1   1      ; "pairs"
+0012    TDUP     2   2
+0013    CALL     1   4   2
+0014    ISNEXT   4 => 0016
+0015 => ADDVV    0   0   5
+0016 => ITERN    4   3   3
+0017    ITERL    4 => 0015
+0018    TNEW     1   0
+0019    GGET     2   3      ; "next"
+0020    MOV      3   1
+0021    KPRI     4   0
+0022    ISNEXT   5 => 0024
+0023 => ADDVV    0   0   6
+0024 => ITERN    5   3   3
+0025    ITERL    5 => 0023
+0026    RET1     0   2
+
+-- BYTECODE -- calls.lua:16-19
+0001    VARG     0   3   0
+0002    UGET     2   0      ; sum
+0003    MOV      3   0
+0004    MOV      4   1
+0005    VARG     5   0   0
+0006    CALLMT   2   2
+
+-- BYTECODE -- calls.lua:0-23
+0001    FNEW     0   0      ; calls.lua:1
+0002    FNEW     1   1      ; calls.lua:16
+0003    GGET     2   2      ; "print"
+0004    MOV      3   1
+0005    KSHORT   4   1
+0006    KSHORT   5   2
+0007    KSHORT   6   3
+0008    CALL     3   0   4
+0009    CALLM    2   1   0
+0010    MOV      2   0
+0011    GGET     3   3      ; "select"
+0012    KSHORT   4   2
+0013    KSHORT   5   1
+0014    KSHORT   6   2
+0015    CALL     3   0   4
+0016    UCLO     0 => 0017
+0017 => CALLMT   2   0
+
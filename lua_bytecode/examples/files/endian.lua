@@ -0,0 +1,17 @@
+local big = 0x123456789ULL
+local signed = -0x123456789LL
+local complex = 1.5 + 2.25i
+local template = { 0.1, 2.5e300, -3.75, x = 1.125, [0.5] = "half", "s" }
+
+local function scale(value)
+  local factor = 1234567.891
+  return value * factor + big, template
+end
+
+local count = 0
+local function bump(step)
+  count = count + step + 0.25
+  return function() return count, signed, complex end
+end
+
+return scale(3.5), bump(2)
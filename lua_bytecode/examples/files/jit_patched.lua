@@ -0,0 +1,38 @@
+local function loops(n, t)
+  local s = 0
+  for i = 1, n do s = s + i end
+  local k = 0
+  while k < n do k = k + 1; s = s + k end
+  repeat k = k - 1 until k <= 0
+  for _, v in pairs(t) do s = s + v end
+  for i = n, 1, -1 do
+    local j = 0
+    while j < 3 do j = j + 1 end
+    s = s + i * j
+  end
+  return s
+end
+
+local t = {}
+for i = 1, 100 do t[i] = i end
+for _ = 1, 200 do loops(100, t) end
+
+-- `string.dump` undoes the patches, `jit.util.funcbc` shows them
+local jutil = require("jit.util")
+local bit = require("bit")
+
+local function word(ins)
+  return string.char(bit.band(ins, 0xff), bit.band(bit.rshift(ins, 8), 0xff),
+    bit.band(bit.rshift(ins, 16), 0xff), bit.band(bit.rshift(ins, 24), 0xff))
+end
+
+local dump = string.dump(loops, true)
+local patched = {}
+for pc = 1, jutil.funcinfo(loops).bytecodes - 1 do
+  patched[#patched + 1] = word((jutil.funcbc(loops, pc)))
+end
+
+-- The first instruction is never patched, the dumped ones start there
+local body = table.concat(patched)
+local at = dump:find(patched[1], 1, true)
+io.open(arg[1], "wb"):write(dump:sub(1, at - 1) .. body .. dump:sub(at + #body))
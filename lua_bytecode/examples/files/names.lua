@@ -0,0 +1,5 @@
+local a_rather_long_upvalue_name = 1
+
+return function()
+  return a_rather_long_upvalue_name
+end
@@ -0,0 +1,32 @@
+-- BYTECODE -- operands.lua:1-3
+0001    TNEW     0   3
+0002    VARG     1   0   0
+0003    TSETM    1   0      ; 1
+0004    RET1     0   2
+
+-- BYTECODE -- operands.lua:5-7
+0001    TDUP     0   0
+0002    VARG     1   0   0
+0003    TSETM    1   0      ; 3
+0004    RET1     0   2
+
+-- BYTECODE -- operands.lua:9-11
+0001    MOV      1   0
+0002    KPRI     3   0
+0003    KPRI     4   1
+0004    KPRI     5   2
+0005    CALL     1   2   4
+0006    KSHORT   2 300
+0007    KNUM     3   0      ; 1.5
+0008    RET      1   4
+
+-- BYTECODE -- operands.lua:0-14
+0001    FNEW     0   0      ; operands.lua:1
+0002    FNEW     1   1      ; operands.lua:5
+0003    FNEW     2   2      ; operands.lua:9
+0004    MOV      3   0
+0005    MOV      4   1
+0006    MOV      5   2
+0007    UCLO     0 => 0008
+0008 => RET      3   4
+
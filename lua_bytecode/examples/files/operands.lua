@@ -0,0 +1,13 @@
+local function first(...)
+  return { ... }
+end
+
+local function rest(...)
+  return { "head", 2, ... }
+end
+
+local function flags(callback)
+  return callback(nil, false, true), 300, 1.5
+end
+
+return first, rest, flags
@@ -0,0 +1,76 @@
+use lua_bytecode::decoder::{
+    error::Result,
+    luajit::{
+        instruction::{InstructionOperand, InstructionOperands},
+        DecodedLuaJitBytecode,
+    },
+};
+
+/// Walks the instructions of `calls.lua`, printing what the decoder resolves for each one
+fn main() -> Result<()> {
+    let raw_file = std::include_bytes!("./files/calls_fr2");
+    let bytecode = DecodedLuaJitBytecode::from_slice(raw_file)?;
+
+    for prototype in &bytecode.prototypes {
+        println!("lines {:?}", prototype.line_range());
+        let labels = prototype.labels();
+
+        for (index, instruction) in prototype.instructions().iter().enumerate() {
+            let pc = index + 1;
+            // The same for dumps of any version
+            let opcode = instruction.opcode.canonical();
+            let label = labels.name(pc as u32).unwrap_or_default();
+            let line = prototype.line_number(pc).unwrap_or_default();
+            print!("{label:>4} {pc:04} {line:>3} {opcode:<6}");
+
+            if let Some(target) = prototype.branch_target(pc) {
+                print!(" -> {}", labels.name(target).unwrap_or_default());
+            }
+
+            let kinds: Vec<_> = [
+                (opcode.is_branch(), "branch"),
+                (opcode.is_call(), "call"),
+                (opcode.is_return(), "return"),
+                (opcode.is_loop(), "loop"),
+            ]
+            .into_iter()
+            .filter_map(|(is_kind, kind)| is_kind.then_some(kind))
+            .collect();
+            if !kinds.is_empty() {
+                print!(" [{}]", kinds.join(", "));
+            }
+
+            if let Some(value) = instruction.resolve_operand(prototype)? {
+                print!(" {value:?}");
+            } else if let Some(constant) = instruction.resolve_gc_constant(prototype)? {
+                print!(" {constant:?}");
+            }
+
+            if let Some(slots) = instruction.call_slots(&bytecode.header) {
+                print!(" {slots:?}");
+            }
+
+            let operands = match &instruction.operands {
+                InstructionOperands::Abc { a, b, c } => vec![a, b, c],
+                InstructionOperands::Ad { a, d } => vec![a, d],
+            };
+            let locals: Vec<_> = operands
+                .into_iter()
+                .flatten()
+                .filter_map(|operand| match operand {
+                    InstructionOperand::Slot(slot) => prototype.local_at(*slot, pc),
+                    _ => None,
+                })
+                .map(|variable| variable.name().to_string())
+                .collect();
+            if !locals.is_empty() {
+                print!(" ; {}", locals.join(", "));
+            }
+
+            println!();
+        }
+        println!();
+    }
+
+    Ok(())
+}
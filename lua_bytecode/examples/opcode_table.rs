@@ -0,0 +1,40 @@
+use lua_bytecode::decoder::{
+    error::Result,
+    luajit::{
+        options::DecodeOptions,
+        revision::{LuaJitRevision, OpcodeTable},
+        DecodedLuaJitBytecode,
+    },
+};
+
+fn main() -> Result<()> {
+    let raw_file = std::include_bytes!("./files/compiled_1");
+
+    // A made up fork of LuaJIT 2.1 that swapped `ADDVV` and `SUBVV`
+    let names = LuaJitRevision::LuaJit2_1
+        .opcode_table()
+        .opcodes()
+        .iter()
+        .map(|opcode| match opcode.to_string().as_str() {
+            "ADDVV" => "SUBVV".to_owned(),
+            "SUBVV" => "ADDVV".to_owned(),
+            name => name.to_owned(),
+        })
+        .collect::<Vec<_>>();
+    let fork =
+        OpcodeTable::from_names(LuaJitRevision::LuaJit2_1, names.iter().map(String::as_str))?;
+
+    for opcodes in [LuaJitRevision::OpenResty.opcode_table().clone(), fork] {
+        let options = DecodeOptions {
+            opcodes: Some(opcodes),
+            ..Default::default()
+        };
+        let decoded = DecodedLuaJitBytecode::from_slice_with_options(raw_file, &options)?;
+
+        // `function add(a, b)` ends with `ADDVV 2 0 1` when decoded with the upstream numbering
+        let instruction = &decoded.prototypes[0].instructions()[6];
+        println!("{}", instruction.opcode);
+    }
+
+    Ok(())
+}
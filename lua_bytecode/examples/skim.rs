@@ -0,0 +1,44 @@
+use lua_bytecode::decoder::{
+    error::Result,
+    luajit::{constants::ComplexConstantValue, skim::PrototypeIndex, stream::PrototypeReader},
+};
+
+/// Finds prototypes without decoding the whole dump
+fn main() -> Result<()> {
+    let raw_file = std::include_bytes!("./files/compiled_1");
+
+    // Only the sizes are read to locate every prototype
+    let index = PrototypeIndex::from_slice(raw_file)?;
+    println!("chunk: {:?}", index.header.chunk_name);
+    for (number, location) in index.prototypes.iter().enumerate() {
+        println!(
+            "prototype {number}: {:#06x}..{:#06x} ({} bytes)",
+            location.offset,
+            location.end(),
+            location.size
+        );
+    }
+
+    // Stops at the function returning "Monday: ..." without decoding the rest of the file
+    let mut reader = PrototypeReader::new(&raw_file[..])?;
+    let uses_monday = |constant: &ComplexConstantValue| matches!(constant, ComplexConstantValue::String(string) if string.as_bytes().starts_with(b"Monday"));
+    let found = reader.by_ref().find(|prototype| match prototype {
+        Ok(prototype) => prototype
+            .constants()
+            .complex_constants
+            .iter()
+            .any(uses_monday),
+        Err(_) => true,
+    });
+
+    if let Some(prototype) = found.transpose()? {
+        println!(
+            "found the function at lines {:?}, {} of {} bytes read",
+            prototype.line_range(),
+            reader.position(),
+            raw_file.len()
+        );
+    }
+
+    Ok(())
+}
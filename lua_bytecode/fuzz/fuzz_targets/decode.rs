@@ -0,0 +1,59 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use lua_bytecode::decoder::luajit::{skim::PrototypeIndex, DecodedLuaJitBytecode};
+
+// Run with `cargo +nightly fuzz run decode`, `corpus/decode` holds the seeds and past regressions
+//
+// Any input has to be rejected with an `Err`, never with a panic or an abort
+fuzz_target!(|data: &[u8]| {
+    let streamed = DecodedLuaJitBytecode::from_read(data);
+    let borrowed = DecodedLuaJitBytecode::from_slice(data);
+
+    // Both go through the same decoder and have to agree
+    let bytecode = match (borrowed, streamed) {
+        (Ok(borrowed), Ok(streamed)) => {
+            // Compared through their encoding, as NaN constants never compare equal
+            assert_eq!(encode(&borrowed), encode(&streamed));
+            borrowed
+        }
+        (Err(_), Err(_)) => return,
+        (borrowed, streamed) => panic!(
+            "slice and stream decoding disagree: {:?} / {:?}",
+            borrowed.err(),
+            streamed.err()
+        ),
+    };
+
+    // Every prototype checks its declared size, so skimming finds the same ones
+    let index = PrototypeIndex::from_slice(data).expect("skimming a decodable dump");
+    assert_eq!(index.prototypes.len(), bytecode.prototypes.len());
+
+    // Whatever was accepted has to be printable as well
+    let _ = bytecode.disassemble();
+    for prototype in &bytecode.prototypes {
+        let labels = prototype.labels();
+        for (pc, instruction) in (1..).zip(prototype.instructions()) {
+            if let Some(target) = prototype.branch_target(pc) {
+                assert!(labels.is_target(target));
+            }
+            if let Some(slots) = instruction.call_slots(&bytecode.header) {
+                // Only `VARG` has neither a function nor arguments
+                assert_eq!(slots.function.is_none(), slots.arguments.is_none());
+            }
+            let _ = instruction.resolve_operand(prototype);
+            let _ = instruction.resolve_gc_constant(prototype);
+        }
+    }
+
+    // Normalising only swaps instruction words, so the result is always encodable
+    let mut normalised = bytecode.clone();
+    normalised.normalise();
+    assert_eq!(encode(&normalised).is_some(), encode(&bytecode).is_some());
+});
+
+fn encode(bytecode: &DecodedLuaJitBytecode) -> Option<Vec<u8>> {
+    let mut encoded = vec![];
+    bytecode.write_to(&mut encoded).ok()?;
+    Some(encoded)
+}
@@ -1,3 +1,4 @@
+use std::fmt;
 use thiserror::Error;
 
 pub type Result<T> = core::result::Result<T, Error>;
@@ -25,25 +26,233 @@ pub enum Error {
     #[error("Invalid opcode number: {0:#x}")]
     LuaJitInvalidOpcodeNumber(u32),
 
+    #[error("Luajit: unknown opcode name: {0}")]
+    LuaJitUnknownOpcodeName(String),
+
+    #[error("Luajit: invalid primitive operand: {0}")]
+    LuaJitInvalidPrimitive(u16),
+
+    #[error("Luajit: numeric constant {index} out of range, the prototype has {count}")]
+    LuaJitNumericConstantOutOfRange { index: u16, count: usize },
+
+    #[error("Luajit: GC constant {index} out of range, the prototype has {count}")]
+    LuaJitGcConstantOutOfRange { index: u16, count: usize },
+
+    #[error("Luajit: GC constant {index} is not a {expected}")]
+    LuaJitGcConstantKindMismatch { index: u16, expected: &'static str },
+
+    #[error(
+        "Luajit: jump at pc {pc} with operand {operand:#x} leads before the start of the function"
+    )]
+    LuaJitInvalidJumpTarget { pc: u32, operand: u16 },
+
+    #[error("Luajit: uleb128 value doesn't fit in 32 bits")]
+    LuaJitUleb128Overflow,
+
     #[error("Ivalid debug variable type: {0}")]
     LuaJitInvalidDebugVariableType(u8),
 
+    #[error("Luajit: child prototype referenced, but none is left to claim")]
+    LuaJitMissingChildPrototype,
+
+    #[error(
+        "Luajit: prototype declares a size of {declared} bytes, but its contents take {consumed}"
+    )]
+    LuaJitPrototypeSizeMismatch { declared: u64, consumed: u64 },
+
+    #[error("Luajit: {0} prototype(s) not referenced by any parent")]
+    LuaJitDanglingPrototypes(usize),
+
+    #[error("Luajit: {limit} limit of {max} exceeded: {value}")]
+    LuaJitLimitExceeded {
+        limit: DecodeLimit,
+        value: u64,
+        max: u64,
+    },
+
     #[error("An error occured while converting from {0} to {1}")]
     ConvertError(&'static str, &'static str),
 
+    #[error("{source} ({context})")]
+    Decode {
+        context: DecodeContext,
+        source: Box<Error>,
+    },
+
     // Froms
     #[error(transparent)]
     IoError(#[from] std::io::Error),
 
     #[error(transparent)]
-    FromUtf8Error(#[from] std::string::FromUtf8Error),
+    TryFromIntError(#[from] core::num::TryFromIntError),
+}
 
-    #[error(transparent)]
-    FromBytesWithNulError(#[from] std::ffi::FromBytesWithNulError),
+/// Where in the input an error happened
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct DecodeContext {
+    /// Bytes consumed from the input when decoding failed
+    pub offset: u64,
+    pub section: Option<DecodeSection>,
+    /// Index of the prototype in dump order
+    pub prototype: Option<usize>,
+    /// Position of the instruction, `1` being the first dumped one
+    pub pc: Option<u32>,
+}
 
-    #[error(transparent)]
-    Utf8Error(#[from] core::str::Utf8Error),
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DecodeSection {
+    Header,
+    PrototypeHeader,
+    Instructions,
+    Constants,
+    DebugInfo,
+}
 
-    #[error(transparent)]
-    TryFromIntError(#[from] core::num::TryFromIntError),
+/// A problem skipped over while decoding leniently, see [`DecodeOptions::lenient`]
+///
+/// [`DecodeOptions::lenient`]: super::luajit::options::DecodeOptions::lenient
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Diagnostic {
+    pub context: DecodeContext,
+    /// Description of the error that was recovered from
+    pub message: String,
+}
+
+/// The [`DecodeOptions`] field a dump went over
+///
+/// [`DecodeOptions`]: super::luajit::options::DecodeOptions
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DecodeLimit {
+    FileSize,
+    Prototypes,
+    Instructions,
+    Constants,
+    StringLength,
+    NestingDepth,
+}
+
+impl Error {
+    /// The location details attached to this error, if any
+    pub fn context(&self) -> Option<&DecodeContext> {
+        match self {
+            Error::Decode { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+
+    /// The error without the location details
+    pub fn inner(&self) -> &Error {
+        match self {
+            Error::Decode { source, .. } => source.inner(),
+            error => error,
+        }
+    }
+
+    /// Turns the error into a diagnostic, keeping its location details
+    pub(crate) fn into_diagnostic(self) -> Diagnostic {
+        let message = self.inner().to_string();
+        let context = self.context().cloned().unwrap_or_default();
+        Diagnostic { context, message }
+    }
+
+    /// Swaps the error for `source`, keeping the location details
+    pub(crate) fn with_source(self, source: Error) -> Self {
+        match self {
+            Error::Decode { context, .. } => Error::Decode {
+                context,
+                source: Box::new(source),
+            },
+            _ => source,
+        }
+    }
+
+    pub(crate) fn in_section(self, section: DecodeSection) -> Self {
+        self.with_context(|context| {
+            context.section.get_or_insert(section);
+        })
+    }
+
+    pub(crate) fn at_pc(self, pc: u32) -> Self {
+        self.with_context(|context| {
+            context.section.get_or_insert(DecodeSection::Instructions);
+            context.pc.get_or_insert(pc);
+        })
+    }
+
+    pub(crate) fn at_offset(self, offset: u64, prototype: Option<usize>) -> Self {
+        self.with_context(|context| {
+            context.offset = offset;
+            context.prototype = context.prototype.or(prototype);
+        })
+    }
+
+    /// Fills in details, keeping the more specific ones attached closer to the failure
+    fn with_context(self, update: impl FnOnce(&mut DecodeContext)) -> Self {
+        match self {
+            Error::Decode {
+                mut context,
+                source,
+            } => {
+                update(&mut context);
+                Error::Decode { context, source }
+            }
+            source => {
+                let mut context = DecodeContext::default();
+                update(&mut context);
+                Error::Decode {
+                    context,
+                    source: Box::new(source),
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for DecodeContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "at offset {:#x}", self.offset)?;
+        if let Some(prototype) = self.prototype {
+            write!(f, ", prototype {prototype}")?;
+        }
+        if let Some(section) = self.section {
+            write!(f, ", {section}")?;
+        }
+        if let Some(pc) = self.pc {
+            write!(f, ", pc {pc}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.message, self.context)
+    }
+}
+
+impl fmt::Display for DecodeSection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DecodeSection::Header => "header",
+            DecodeSection::PrototypeHeader => "prototype header",
+            DecodeSection::Instructions => "instructions",
+            DecodeSection::Constants => "constants",
+            DecodeSection::DebugInfo => "debug info",
+        };
+        f.write_str(name)
+    }
+}
+
+impl fmt::Display for DecodeLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DecodeLimit::FileSize => "file size",
+            DecodeLimit::Prototypes => "prototype count",
+            DecodeLimit::Instructions => "instruction count",
+            DecodeLimit::Constants => "constant count",
+            DecodeLimit::StringLength => "string length",
+            DecodeLimit::NestingDepth => "nesting depth",
+        };
+        f.write_str(name)
+    }
 }
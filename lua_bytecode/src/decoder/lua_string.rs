@@ -0,0 +1,147 @@
+use std::{borrow::Cow, fmt};
+
+/// A Lua string
+///
+/// Lua strings are arbitrary bytes: binary blobs, Latin-1 text or encrypted data decode just as
+/// well as UTF-8. [`fmt::Display`] replaces invalid sequences, [`LuaString::escape`] keeps every
+/// byte.
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LuaString<'a>(Cow<'a, [u8]>);
+
+impl<'a> LuaString<'a> {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn into_bytes(self) -> Cow<'a, [u8]> {
+        self.0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The string as text, `None` if it isn't valid UTF-8
+    pub fn to_str(&self) -> Option<&str> {
+        std::str::from_utf8(&self.0).ok()
+    }
+
+    /// The string as text, with invalid UTF-8 sequences replaced by `U+FFFD`
+    pub fn to_string_lossy(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.0)
+    }
+
+    /// Displays the string as the body of a Lua string literal
+    ///
+    /// Valid UTF-8 text is kept as is, quotes, backslashes and control characters are escaped and
+    /// any other byte is written as a `\ddd` decimal escape.
+    pub fn escape(&self) -> Escape<'_> {
+        Escape(&self.0)
+    }
+
+    pub fn into_owned(self) -> LuaString<'static> {
+        LuaString(Cow::Owned(self.0.into_owned()))
+    }
+}
+
+/// Return value of [`LuaString::escape`]
+#[derive(Clone, Copy, Debug)]
+pub struct Escape<'s>(&'s [u8]);
+
+impl fmt::Display for Escape<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for chunk in self.0.utf8_chunks() {
+            for char in chunk.valid().chars() {
+                match char {
+                    '\n' => f.write_str("\\n")?,
+                    '\r' => f.write_str("\\r")?,
+                    '\t' => f.write_str("\\t")?,
+                    '\\' => f.write_str("\\\\")?,
+                    '"' => f.write_str("\\\"")?,
+                    char if char.is_control() => {
+                        let mut buf = [0; 4];
+                        for byte in char.encode_utf8(&mut buf).bytes() {
+                            write!(f, "\\{byte:03}")?;
+                        }
+                    }
+                    char => write!(f, "{char}")?,
+                }
+            }
+
+            for byte in chunk.invalid() {
+                write!(f, "\\{byte:03}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for LuaString<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_string_lossy())
+    }
+}
+
+impl fmt::Debug for LuaString<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\"{}\"", self.escape())
+    }
+}
+
+impl AsRef<[u8]> for LuaString<'_> {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl<'a> From<Cow<'a, [u8]>> for LuaString<'a> {
+    fn from(bytes: Cow<'a, [u8]>) -> Self {
+        Self(bytes)
+    }
+}
+
+impl<'a> From<&'a [u8]> for LuaString<'a> {
+    fn from(bytes: &'a [u8]) -> Self {
+        Self(Cow::Borrowed(bytes))
+    }
+}
+
+impl From<Vec<u8>> for LuaString<'_> {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self(Cow::Owned(bytes))
+    }
+}
+
+impl<'a> From<&'a str> for LuaString<'a> {
+    fn from(string: &'a str) -> Self {
+        Self(Cow::Borrowed(string.as_bytes()))
+    }
+}
+
+impl From<String> for LuaString<'_> {
+    fn from(string: String) -> Self {
+        Self(Cow::Owned(string.into_bytes()))
+    }
+}
+
+impl PartialEq<[u8]> for LuaString<'_> {
+    fn eq(&self, other: &[u8]) -> bool {
+        self.as_bytes() == other
+    }
+}
+
+impl PartialEq<str> for LuaString<'_> {
+    fn eq(&self, other: &str) -> bool {
+        self.as_bytes() == other.as_bytes()
+    }
+}
+
+impl PartialEq<&str> for LuaString<'_> {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_bytes() == other.as_bytes()
+    }
+}
@@ -1,23 +1,36 @@
-use super::{get_uleb128_33, read_uleb128, Result};
-use crate::decoder::util::{self, Endianness};
+use super::{
+    get_uleb128_33, options::DecodeOptions, read_uleb128, table::LuaJitTable, write_uleb128,
+    write_uleb128_33, Error, Result,
+};
+use crate::decoder::{
+    lua_string::LuaString,
+    util::{self, Endianness, Source},
+};
 
-use std::io::Read;
+use std::io::{Read, Write};
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
-pub struct Table;
-
-#[derive(Clone, Debug, PartialEq)]
-pub struct LuajitConstants {
-    pub up_value_references: Vec<u8>,
-    pub complex_constants: Vec<ComplexConstantValue>,
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LuajitConstants<'a> {
+    /// Parent slot or upvalue each upvalue is captured from, see [`LuajitConstants::UPVALUE_LOCAL`]
+    pub up_value_references: Vec<u16>,
+    pub complex_constants: Vec<ComplexConstantValue<'a>>,
     pub numeric_constants: Vec<LuaJitNumericConstant>,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
-pub enum ComplexConstantValue {
-    String(String),
-    Table,
+#[derive(Clone, Debug, PartialEq)]
+pub enum ComplexConstantValue<'a> {
+    String(LuaString<'a>),
+    Table(LuaJitTable<'a>),
+    /// Nested function, as an index into [`DecodedLuaJitBytecode::prototypes`]
+    ///
+    /// [`DecodedLuaJitBytecode::prototypes`]: super::DecodedLuaJitBytecode::prototypes
     Child(u32),
+    /// `int64_t` cdata, e.g. `123LL`
+    I64(i64),
+    /// `uint64_t` cdata, e.g. `0xffULL`
+    U64(u64),
+    /// `complex` cdata as `(real, imaginary)`, e.g. `1i`
+    Complex(f64, f64),
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -26,7 +39,7 @@ pub enum LuaJitNumericConstant {
     Number(f64),
 }
 
-/// Private since encoder is not implemented yet
+/// Tag in front of each GC constant, see `BCDUMP_KGC_*` in `lj_bcdump.h`
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 enum ConstantTypeRaw {
     Child = 0,
@@ -37,30 +50,37 @@ enum ConstantTypeRaw {
     Str = 5,
 }
 
-impl LuajitConstants {
-    pub(super) fn from_read<R: Read>(
-        r: &mut R,
+impl<'a> LuajitConstants<'a> {
+    /// Set in an upvalue reference when it captures a local slot of the parent function, rather
+    /// than one of the parent's upvalues
+    pub const UPVALUE_LOCAL: u16 = 0x8000;
+    /// Set in an upvalue reference when the captured variable is never reassigned
+    pub const UPVALUE_IMMUTABLE: u16 = 0x4000;
+
+    pub(super) fn from_read<S: Source<'a>>(
+        r: &mut S,
         up_values_count: u8,
         complex_constants_count: u32,
         numeric_constants_count: u32,
         endianness: Endianness,
-        prototype_count: &mut u32,
+        child_stack: &mut Vec<u32>,
+        options: &DecodeOptions,
     ) -> Result<Self> {
         let mut up_value_references = Vec::with_capacity(up_values_count.into());
         for _ in 0..up_values_count {
             up_value_references.push(util::get_varying_size_num(r, 2usize, endianness)?.try_into()?)
         }
 
-        let mut complex_constants = Vec::with_capacity(complex_constants_count as usize);
+        // The counts come straight from the input, so nothing is reserved based on them
+        let mut complex_constants = vec![];
         for _ in 0..complex_constants_count {
-            let complex_constant =
-                ComplexConstantValue::from_read(r, &endianness, prototype_count)?;
+            let complex_constant = ComplexConstantValue::from_read(r, child_stack, options)?;
             complex_constants.push(complex_constant);
         }
 
-        let mut numeric_constants = Vec::with_capacity(numeric_constants_count as usize);
+        let mut numeric_constants = vec![];
         for _ in 0..numeric_constants_count {
-            let numeric_constant = LuaJitNumericConstant::from_read(r, &endianness)?;
+            let numeric_constant = LuaJitNumericConstant::from_read(r)?;
             numeric_constants.push(numeric_constant);
         }
 
@@ -71,29 +91,76 @@ impl LuajitConstants {
         };
         Ok(constants)
     }
+
+    pub fn into_owned(self) -> LuajitConstants<'static> {
+        LuajitConstants {
+            up_value_references: self.up_value_references,
+            complex_constants: self
+                .complex_constants
+                .into_iter()
+                .map(ComplexConstantValue::into_owned)
+                .collect(),
+            numeric_constants: self.numeric_constants,
+        }
+    }
+
+    pub(super) fn write_to<W: Write>(&self, w: &mut W, endianness: &Endianness) -> Result<()> {
+        for up_value_reference in &self.up_value_references {
+            util::write_varying_size_num(w, (*up_value_reference).into(), 2usize, *endianness)?;
+        }
+
+        for complex_constant in &self.complex_constants {
+            complex_constant.write_to(w)?;
+        }
+
+        for numeric_constant in &self.numeric_constants {
+            numeric_constant.write_to(w)?;
+        }
+
+        Ok(())
+    }
+
+    /// Looks up a string, table, function or cdata operand, which index the pool from its end
+    pub(super) fn gc_constant(&self, operand: u16) -> Option<&ComplexConstantValue<'a>> {
+        let index = self
+            .complex_constants
+            .len()
+            .checked_sub(usize::from(operand) + 1)?;
+        self.complex_constants.get(index)
+    }
 }
 
-impl ComplexConstantValue {
-    fn from_read<R: Read>(
-        r: &mut R,
-        endianness: &Endianness,
-        prototype_count: &mut u32,
+impl<'a> ComplexConstantValue<'a> {
+    fn from_read<S: Source<'a>>(
+        r: &mut S,
+        child_stack: &mut Vec<u32>,
+        options: &DecodeOptions,
     ) -> Result<Self> {
         let constant_type_raw = read_uleb128(r)?;
         let constant_type = ConstantTypeRaw::from(constant_type_raw);
 
         let complex_constant_value = match constant_type {
             ConstantTypeRaw::Child => {
-                // FIXME: I don't know how this works (yet) . So don't trust this
-                *prototype_count -= 1;
-                ComplexConstantValue::Child(*prototype_count)
+                // Children are dumped before their parent and referenced in reverse order,
+                // so each reference takes the most recently decoded unclaimed prototype
+                let child = child_stack
+                    .pop()
+                    .ok_or(Error::LuaJitMissingChildPrototype)?;
+                ComplexConstantValue::Child(child)
+            }
+            ConstantTypeRaw::Tab => {
+                ComplexConstantValue::Table(LuaJitTable::read_table(r, options)?)
+            }
+            ConstantTypeRaw::I64 => ComplexConstantValue::I64(read_u64(r)? as i64),
+            ConstantTypeRaw::U64 => ComplexConstantValue::U64(read_u64(r)?),
+            ConstantTypeRaw::Complex => {
+                let real = f64::from_bits(read_u64(r)?);
+                let imaginary = f64::from_bits(read_u64(r)?);
+                ComplexConstantValue::Complex(real, imaginary)
             }
-            ConstantTypeRaw::Tab => todo!(),
-            ConstantTypeRaw::I64 => todo!(),
-            ConstantTypeRaw::U64 => todo!(),
-            ConstantTypeRaw::Complex => todo!(),
             ConstantTypeRaw::Str => {
                 let length = constant_type_raw - ConstantTypeRaw::Str as u32;
+                options.check_string_length(length)?;
                 let string = util::read_string(r, length as usize)?;
                 ComplexConstantValue::String(string)
             }
@@ -101,40 +168,118 @@ impl ComplexConstantValue {
 
         Ok(complex_constant_value)
     }
-}
 
-impl LuaJitNumericConstant {
-    fn from_read<R: Read>(r: &mut R, endianness: &Endianness) -> Result<Self> {
-        let (is_num, lo) = get_uleb128_33(r)?;
+    fn write_to<W: Write>(&self, w: &mut W) -> Result<()> {
+        match self {
+            ComplexConstantValue::String(string) => {
+                let length: u32 = string.len().try_into()?;
+                let constant_type = length
+                    .checked_add(ConstantTypeRaw::Str as u32)
+                    .ok_or(Error::ConvertError("usize", "string constant type"))?;
+                write_uleb128(w, constant_type)?;
+                w.write_all(string.as_bytes())?;
+            }
+            ComplexConstantValue::Table(table) => {
+                write_uleb128(w, ConstantTypeRaw::Tab as u32)?;
+                table.write_to(w)?;
+            }
+            ComplexConstantValue::Child(_) => write_uleb128(w, ConstantTypeRaw::Child as u32)?,
+            ComplexConstantValue::I64(value) => {
+                write_uleb128(w, ConstantTypeRaw::I64 as u32)?;
+                write_u64(w, *value as u64)?;
+            }
+            ComplexConstantValue::U64(value) => {
+                write_uleb128(w, ConstantTypeRaw::U64 as u32)?;
+                write_u64(w, *value)?;
+            }
+            ComplexConstantValue::Complex(real, imaginary) => {
+                write_uleb128(w, ConstantTypeRaw::Complex as u32)?;
+                write_u64(w, real.to_bits())?;
+                write_u64(w, imaginary.to_bits())?;
+            }
+        }
 
-        let bc_k_num = if is_num {
-            let hi: u32 = read_uleb128(r)?;
+        Ok(())
+    }
 
-            let lo: u64 = lo.into();
-            let hi: u64 = hi.into();
+    pub fn into_owned(self) -> ComplexConstantValue<'static> {
+        match self {
+            Self::String(string) => ComplexConstantValue::String(string.into_owned()),
+            Self::Table(table) => ComplexConstantValue::Table(table.into_owned()),
+            Self::Child(index) => ComplexConstantValue::Child(index),
+            Self::I64(value) => ComplexConstantValue::I64(value),
+            Self::U64(value) => ComplexConstantValue::U64(value),
+            Self::Complex(real, imaginary) => ComplexConstantValue::Complex(real, imaginary),
+        }
+    }
 
-            let proper_number = match endianness {
-                Endianness::BigEndian => lo << 32 | hi,
-                Endianness::LittleEndian => hi << 32 | lo,
-            };
+    /// Whether this constant is an FFI cdata value (the operand of `KCDATA`)
+    pub fn is_cdata(&self) -> bool {
+        matches!(self, Self::I64(_) | Self::U64(_) | Self::Complex(..))
+    }
+}
 
-            let raw_bytes = match endianness {
-                Endianness::BigEndian => proper_number.to_be_bytes(),
-                Endianness::LittleEndian => proper_number.to_le_bytes(),
-            };
+impl LuaJitNumericConstant {
+    /// The constant as a Lua number
+    pub fn value(&self) -> f64 {
+        match self {
+            // Integers are dumped as the bits of an `int32_t`
+            Self::Int(int) => f64::from(*int as i32),
+            Self::Number(number) => *number,
+        }
+    }
 
-            let resulting = match endianness {
-                Endianness::BigEndian => f64::from_be_bytes(raw_bytes),
-                Endianness::LittleEndian => f64::from_le_bytes(raw_bytes),
-            };
+    fn from_read<R: Read>(r: &mut R) -> Result<Self> {
+        let (is_num, lo) = get_uleb128_33(r)?;
 
-            Self::Number(resulting)
+        let bc_k_num = if is_num {
+            let hi: u32 = read_uleb128(r)?;
+            Self::Number(f64::from_bits(join_halves(lo, hi)))
         } else {
             Self::Int(lo)
         };
 
         Ok(bc_k_num)
     }
+
+    fn write_to<W: Write>(&self, w: &mut W) -> Result<()> {
+        match self {
+            Self::Int(value) => write_uleb128_33(w, false, *value),
+            Self::Number(value) => {
+                let (lo, hi) = split_halves(value.to_bits());
+                write_uleb128_33(w, true, lo)?;
+                write_uleb128(w, hi)
+            }
+        }
+    }
+}
+
+/// Reads a 64-bit value stored as two uleb128 encoded 32-bit halves
+fn read_u64<R: Read>(r: &mut R) -> Result<u64> {
+    let lo = read_uleb128(r)?;
+    let hi = read_uleb128(r)?;
+    Ok(join_halves(lo, hi))
+}
+
+fn write_u64<W: Write>(w: &mut W, value: u64) -> Result<()> {
+    let (lo, hi) = split_halves(value);
+    write_uleb128(w, lo)?;
+    write_uleb128(w, hi)
+}
+
+/// Joins the low and high words of a 64-bit constant
+///
+/// LuaJIT always dumps the low word first, whatever [`HeaderFlags::BCDUMP_F_BE`] says: the words
+/// are written as numbers, not as the bytes of the value.
+///
+/// [`HeaderFlags::BCDUMP_F_BE`]: super::header::HeaderFlags::BCDUMP_F_BE
+pub(super) fn join_halves(lo: u32, hi: u32) -> u64 {
+    u64::from(hi) << 32 | u64::from(lo)
+}
+
+/// Inverse of [`join_halves`], returns `(lo, hi)`
+pub(super) fn split_halves(value: u64) -> (u32, u32) {
+    (value as u32, (value >> 32) as u32)
 }
 
 impl From<u32> for ConstantTypeRaw {
@@ -1,22 +1,40 @@
-use super::{header::HeaderFlags, Error, Result};
+use super::{
+    header::HeaderFlags, options::DecodeOptions, write_uleb128, DecodeLimit, Error, Result,
+};
 use crate::decoder::{
+    lua_string::LuaString,
     luajit::read_uleb128,
-    util::{self, read_u8, Endianness},
+    util::{self, read_u8, write_u8, Source},
+};
+
+use std::{
+    io::{Read, Write},
+    ops::Range,
 };
 
-use std::io::Read;
+const INTERNAL_VAR_NAMES: [Option<&str>; 7] = [
+    None,
+    Some("<index>"),
+    Some("<limit>"),
+    Some("<step>"),
+    Some("<generator>"),
+    Some("<state>"),
+    Some("<control>"),
+];
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
-struct VariableInfo {
+pub struct VariableInfo<'a> {
     start_addr: u32,
     end_addr: u32,
     variable_visibility_type: VariableVisibility,
-    name: String,
+    name: LuaString<'a>,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
-enum VariableVisibility {
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum VariableVisibility {
+    /// Declared in the source
     Visible = 0,
+    /// Hidden slot created by the compiler, e.g. the `<index>` of a numeric `for`
     Internal = 1,
 }
 
@@ -33,17 +51,40 @@ pub enum InternalVarType {
     Max = 7,
 }
 
-impl VariableInfo {
-    fn read_variable_infos<R: Read>(r: &mut R, endianness: &Endianness) -> Result<Vec<Self>> {
-        const INTERNAL_VAR_NAMES: [Option<&str>; 7] = [
-            None,
-            Some("<index>"),
-            Some("<limit>"),
-            Some("<step>"),
-            Some("<generator>"),
-            Some("<state>"),
-            Some("<control>"),
-        ];
+impl<'a> VariableInfo<'a> {
+    pub fn name(&self) -> &LuaString<'a> {
+        &self.name
+    }
+
+    /// First pc the variable is live at
+    pub fn start_pc(&self) -> u32 {
+        self.start_addr
+    }
+
+    /// First pc after the variable went out of scope
+    pub fn end_pc(&self) -> u32 {
+        self.end_addr
+    }
+
+    /// Pcs the variable is live at
+    pub fn pc_range(&self) -> Range<u32> {
+        self.start_addr..self.end_addr
+    }
+
+    pub fn visibility(&self) -> VariableVisibility {
+        self.variable_visibility_type
+    }
+
+    pub fn into_owned(self) -> VariableInfo<'static> {
+        VariableInfo {
+            start_addr: self.start_addr,
+            end_addr: self.end_addr,
+            variable_visibility_type: self.variable_visibility_type,
+            name: self.name.into_owned(),
+        }
+    }
+
+    fn read_variable_infos<S: Source<'a>>(r: &mut S, options: &DecodeOptions) -> Result<Vec<Self>> {
         let mut last_addr = 0u32;
 
         let mut variable_infos = vec![];
@@ -53,31 +94,29 @@ impl VariableInfo {
             let variable_visibility_type;
 
             if internal_var_type >= InternalVarType::Max as u8 {
-                let prefix = match endianness {
-                    Endianness::BigEndian => internal_var_type.to_le_bytes(),
-                    Endianness::LittleEndian => internal_var_type.to_le_bytes(),
-                };
-                let suffix = read_z_string(r)?;
-
-                // TODO: Find cheaper alternative
-                name = String::from_utf8(prefix.to_vec())? + &suffix;
+                // The type byte is the first character of the name
+                name = read_name_after(r, internal_var_type, options)?;
+
                 variable_visibility_type = VariableVisibility::Visible;
             } else if internal_var_type == InternalVarType::End as u8 {
                 break;
             } else {
-                let index = internal_var_type;
-                // TODO: don't unwrap
                 name = INTERNAL_VAR_NAMES
-                    .get(index as usize)
-                    .unwrap()
-                    .unwrap()
-                    .to_owned();
+                    .get(usize::from(internal_var_type))
+                    .copied()
+                    .flatten()
+                    .map(LuaString::from)
+                    .ok_or(Error::LuaJitInvalidDebugVariableType(internal_var_type))?;
 
                 variable_visibility_type = VariableVisibility::Internal;
             }
 
-            let start_addr = last_addr + read_uleb128(r)?;
-            let end_addr = start_addr + read_uleb128(r)?;
+            let start_addr = last_addr
+                .checked_add(read_uleb128(r)?)
+                .ok_or(Error::ConvertError("u32", "variable start pc"))?;
+            let end_addr = start_addr
+                .checked_add(read_uleb128(r)?)
+                .ok_or(Error::ConvertError("u32", "variable end pc"))?;
 
             last_addr = start_addr;
             let s = Self {
@@ -91,29 +130,94 @@ impl VariableInfo {
         }
         Ok(variable_infos)
     }
+
+    fn write_variable_infos<W: Write>(w: &mut W, variable_infos: &[Self]) -> Result<()> {
+        let mut last_addr = 0u32;
+
+        for variable_info in variable_infos {
+            match variable_info.variable_visibility_type {
+                VariableVisibility::Visible => {
+                    w.write_all(variable_info.name.as_bytes())?;
+                    write_u8(w, 0)?;
+                }
+                VariableVisibility::Internal => {
+                    let internal_var_type = INTERNAL_VAR_NAMES
+                        .iter()
+                        .position(|name| name.is_some_and(|name| variable_info.name == name))
+                        .ok_or(Error::ConvertError("String", "InternalVarType"))?;
+                    write_u8(w, internal_var_type.try_into()?)?;
+                }
+            }
+
+            let start_delta = variable_info
+                .start_addr
+                .checked_sub(last_addr)
+                .ok_or(Error::ConvertError("u32", "variable start delta"))?;
+            let length = variable_info
+                .end_addr
+                .checked_sub(variable_info.start_addr)
+                .ok_or(Error::ConvertError("u32", "variable range length"))?;
+            write_uleb128(w, start_delta)?;
+            write_uleb128(w, length)?;
+
+            last_addr = variable_info.start_addr;
+        }
+
+        write_u8(w, InternalVarType::End as u8)
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
-pub struct DebugInformation {
+pub struct DebugInformation<'a> {
     addr_to_line_map: Vec<u64>,
-    upvalue_variables_names: Vec<String>,
-    variable_infos: Vec<VariableInfo>,
+    upvalue_variables_names: Vec<LuaString<'a>>,
+    variable_infos: Vec<VariableInfo<'a>>,
 }
 
-impl DebugInformation {
-    pub(crate) fn from_read<R: Read>(
-        r: &mut R,
-        line_offset: u32,
+impl<'a> DebugInformation<'a> {
+    /// Absolute source line of every dumped instruction, indexed by `pc - 1`
+    pub fn line_numbers(&self) -> &[u64] {
+        &self.addr_to_line_map
+    }
+
+    pub fn upvalue_names(&self) -> &[LuaString<'a>] {
+        &self.upvalue_variables_names
+    }
+
+    pub fn variable_infos(&self) -> &[VariableInfo<'a>] {
+        &self.variable_infos
+    }
+
+    pub fn into_owned(self) -> DebugInformation<'static> {
+        DebugInformation {
+            addr_to_line_map: self.addr_to_line_map,
+            upvalue_variables_names: self
+                .upvalue_variables_names
+                .into_iter()
+                .map(LuaString::into_owned)
+                .collect(),
+            variable_infos: self
+                .variable_infos
+                .into_iter()
+                .map(VariableInfo::into_owned)
+                .collect(),
+        }
+    }
+
+    pub(crate) fn from_read<S: Source<'a>>(
+        r: &mut S,
+        first_line: u32,
+        lines_count: u32,
         header_flags: &HeaderFlags,
         instructions_count: u32,
         up_values_count: usize,
-        endianness: &Endianness,
+        options: &DecodeOptions,
     ) -> Result<Self> {
         let addr_to_line_map =
-            Self::read_line_info(r, line_offset, header_flags, instructions_count)?;
+            Self::read_line_info(r, first_line, lines_count, header_flags, instructions_count)?;
 
-        let upvalue_variables_names = Self::read_upvalue_names(r, up_values_count)?;
-        let variable_infos = VariableInfo::read_variable_infos(r, endianness)?;
+        let upvalue_variables_names = Self::read_upvalue_names(r, up_values_count, options)?;
+        let variable_infos = VariableInfo::read_variable_infos(r, options)?;
 
         let s = Self {
             addr_to_line_map,
@@ -124,35 +228,59 @@ impl DebugInformation {
         Ok(s)
     }
 
+    pub(crate) fn write_to<W: Write>(
+        &self,
+        w: &mut W,
+        first_line: u32,
+        lines_count: u32,
+        header_flags: &HeaderFlags,
+    ) -> Result<()> {
+        let first_line: u64 = first_line.into();
+        let line_info_size = line_info_size(lines_count);
+
+        for line in &self.addr_to_line_map {
+            let line_number = line
+                .checked_sub(first_line)
+                .ok_or(Error::ConvertError("u64", "line delta"))?;
+            util::write_varying_size_num(w, line_number, line_info_size, header_flags)?;
+        }
+
+        for name in &self.upvalue_variables_names {
+            w.write_all(name.as_bytes())?;
+            write_u8(w, 0)?;
+        }
+
+        VariableInfo::write_variable_infos(w, &self.variable_infos)
+    }
+
     fn read_line_info<R: Read>(
         r: &mut R,
-        line_offset: u32,
+        first_line: u32,
+        lines_count: u32,
         header_flags: &HeaderFlags,
         instructions_count: u32,
     ) -> Result<Vec<u64>> {
-        let line_offset: u64 = line_offset.into();
-        let line_info_size: u8 = if line_offset >= 0x10000 {
-            4
-        } else if line_offset >= 0x100 {
-            2
-        } else {
-            1
-        };
+        let first_line: u64 = first_line.into();
+        let line_info_size = line_info_size(lines_count);
 
-        let mut line_info = Vec::with_capacity(instructions_count as usize);
+        let mut line_info = vec![];
         for _ in 0..instructions_count {
-            // FIXME: Spotted bug here during work with the last successfully decoded prototype
-            let line_number = util::get_varying_size_num(r, line_info_size, header_flags)?;
-            line_info.push(line_offset + line_number);
+            // Lines are dumped relative to the first line of the function
+            let line_delta = util::get_varying_size_num(r, line_info_size, header_flags)?;
+            line_info.push(first_line + line_delta);
         }
 
         Ok(line_info)
     }
 
-    fn read_upvalue_names<R: Read>(r: &mut R, up_values_count: usize) -> Result<Vec<String>> {
+    fn read_upvalue_names<S: Source<'a>>(
+        r: &mut S,
+        up_values_count: usize,
+        options: &DecodeOptions,
+    ) -> Result<Vec<LuaString<'a>>> {
         let mut upvalue_names = Vec::with_capacity(up_values_count);
         for _ in 0..up_values_count {
-            let name = read_z_string(r)?;
+            let name = read_z_string(r, options)?;
             upvalue_names.push(name);
         }
         Ok(upvalue_names)
@@ -177,14 +305,43 @@ impl TryFrom<u8> for InternalVarType {
     }
 }
 
-fn read_z_string<R: Read>(r: &mut R) -> Result<String> {
-    let mut buf = vec![];
-    loop {
-        let byte = read_u8(r)?;
-        if byte == 0 {
-            return Ok(String::from_utf8(buf)?);
-        } else {
-            buf.push(byte);
-        }
+/// Width in bytes of each line info entry, just enough for the line span of the function
+fn line_info_size(lines_count: u32) -> u8 {
+    if lines_count < 0x100 {
+        1
+    } else if lines_count < 0x10000 {
+        2
+    } else {
+        4
+    }
+}
+
+fn read_z_string<'a, S: Source<'a>>(r: &mut S, options: &DecodeOptions) -> Result<LuaString<'a>> {
+    match read_u8(r)? {
+        0 => Ok(LuaString::from(&b""[..])),
+        first => read_name_after(r, first, options),
+    }
+}
+
+/// Reads a zero terminated name whose first byte, `first`, was already consumed, giving up as soon
+/// as it is longer than [`DecodeOptions::max_string_length`]
+fn read_name_after<'a, S: Source<'a>>(
+    r: &mut S,
+    first: u8,
+    options: &DecodeOptions,
+) -> Result<LuaString<'a>> {
+    let Some(max) = options.max_string_length else {
+        return Ok(LuaString::from(r.read_z_block_after(first)?));
+    };
+    let max = u64::from(max);
+
+    match r.read_z_block_within(first, max)? {
+        Some(name) => Ok(LuaString::from(name)),
+        // The rest of the name is never read, so all that's known is that it's too long
+        None => Err(Error::LuaJitLimitExceeded {
+            limit: DecodeLimit::StringLength,
+            value: max + 1,
+            max,
+        }),
     }
 }
@@ -0,0 +1,244 @@
+//! Listings in the format printed by `luajit -bl`
+
+use super::{
+    constants::ComplexConstantValue,
+    instruction::{ArgumentType, LuaJitInstruction, OperandValue},
+    opcodes::LuaJitOpcode,
+    prototype::LuaJitPrototype,
+    DecodedLuaJitBytecode,
+};
+use crate::decoder::lua_string::LuaString;
+
+use std::fmt;
+
+/// String constants longer than this are truncated and marked with `~`
+const MAX_STRING_ANNOTATION: usize = 40;
+
+/// Writes the listing of every prototype, nested functions first, as `luajit -bl` does
+pub fn write_listing<W: fmt::Write>(bytecode: &DecodedLuaJitBytecode, w: &mut W) -> fmt::Result {
+    let chunk_name = bytecode
+        .header
+        .chunk_name
+        .as_ref()
+        .map(LuaString::to_string_lossy);
+    let chunk_name = chunk_name.as_deref();
+
+    // Children are always dumped right before their parent, which is the order the listing
+    // visits them in
+    for prototype in &bytecode.prototypes {
+        write_prototype(bytecode, prototype, chunk_name, w)?;
+    }
+
+    Ok(())
+}
+
+fn write_prototype<W: fmt::Write>(
+    bytecode: &DecodedLuaJitBytecode,
+    prototype: &LuaJitPrototype,
+    chunk_name: Option<&str>,
+    w: &mut W,
+) -> fmt::Result {
+    let first_line = prototype.first_line().unwrap_or(0);
+    let last_line = u64::from(first_line) + u64::from(prototype.lines_count().unwrap_or(0));
+    writeln!(
+        w,
+        "-- BYTECODE -- {}-{last_line}",
+        location(chunk_name, first_line)
+    )?;
+
+    let labels = prototype.labels();
+
+    for (pc, instruction) in (1..).zip(prototype.instructions()) {
+        let prefix = if labels.is_target(pc) { "=>" } else { "  " };
+        write_instruction(bytecode, prototype, chunk_name, pc, prefix, instruction, w)?;
+    }
+
+    writeln!(w)
+}
+
+fn write_instruction<W: fmt::Write>(
+    bytecode: &DecodedLuaJitBytecode,
+    prototype: &LuaJitPrototype,
+    chunk_name: Option<&str>,
+    pc: u32,
+    prefix: &str,
+    instruction: &LuaJitInstruction,
+    w: &mut W,
+) -> fmt::Result {
+    let [a_ty, b_ty, cd_ty]: [Option<ArgumentType>; 3] = (&instruction.opcode).into();
+
+    let raw = instruction.raw();
+    let a = (raw >> 8) & 0xff;
+    let b = raw >> 24;
+    let mut d = raw >> 16;
+
+    let a_column = if a_ty.is_some() {
+        a.to_string()
+    } else {
+        String::new()
+    };
+    let line = format!("{pc:04} {prefix} {:<6} {a_column:>3} ", instruction.opcode);
+
+    if let LuaJitOpcode::Unknown(_) = instruction.opcode {
+        return writeln!(w, "{line}    ; {raw:#010x}");
+    }
+
+    if let Some(target) = instruction.jump_target() {
+        return writeln!(w, "{line}=> {target:04}");
+    }
+
+    if b_ty.is_some() {
+        d &= 0xff;
+    } else if cd_ty.is_none() {
+        return writeln!(w, "{line}");
+    }
+
+    let mut annotation = match &cd_ty {
+        Some(ArgumentType::T_STR) => string_annotation(prototype, instruction),
+        Some(ArgumentType::T_NUM) => number_annotation(prototype, instruction),
+        Some(ArgumentType::T_FUN) => {
+            function_annotation(bytecode, prototype, chunk_name, instruction)
+        }
+        Some(ArgumentType::T_UV) => Some(upvalue_name(prototype, d)),
+        _ => None,
+    };
+
+    if a_ty == Some(ArgumentType::T_UV) {
+        let upvalue = upvalue_name(prototype, a);
+        annotation = Some(match annotation {
+            Some(annotation) => format!("{upvalue} ; {annotation}"),
+            None => upvalue,
+        });
+    }
+
+    match (b_ty, annotation) {
+        (Some(_), Some(annotation)) => writeln!(w, "{line}{b:3} {d:3}  ; {annotation}"),
+        (Some(_), None) => writeln!(w, "{line}{b:3} {d:3}"),
+        (None, Some(annotation)) => writeln!(w, "{line}{d:3}      ; {annotation}"),
+        (None, None) if cd_ty == Some(ArgumentType::T_SLIT) => {
+            writeln!(w, "{line}{:3}", d as u16 as i16)
+        }
+        (None, None) => writeln!(w, "{line}{d:3}"),
+    }
+}
+
+/// `chunk:line` the way LuaJIT names a function
+fn location(chunk_name: Option<&str>, line: u32) -> String {
+    match chunk_name {
+        Some(name) if name.starts_with('@') => {
+            let path = &name[1..];
+            let file_name = path.rsplit(['/', '\\']).next().unwrap_or(path);
+            format!("{file_name}:{line}")
+        }
+        Some(name) if name.starts_with('=') => format!("{}:{line}", &name[1..]),
+        Some(name) => format!("\"{name}\":{line}"),
+        None => format!("?:{line}"),
+    }
+}
+
+fn string_annotation(
+    prototype: &LuaJitPrototype,
+    instruction: &LuaJitInstruction,
+) -> Option<String> {
+    let string = match instruction.resolve_gc_constant(prototype).ok()?? {
+        ComplexConstantValue::String(string) => string.as_bytes(),
+        _ => return None,
+    };
+
+    let mut escaped = vec![];
+    for &byte in string {
+        match byte {
+            b'\n' => escaped.extend_from_slice(b"\\n"),
+            b'\r' => escaped.extend_from_slice(b"\\r"),
+            b'\t' => escaped.extend_from_slice(b"\\t"),
+            0..=0x1f | 0x7f => escaped.extend_from_slice(format!("\\{byte:03}").as_bytes()),
+            _ => escaped.push(byte),
+        }
+    }
+
+    // The length check is done on the raw string, the cut on the escaped one
+    let annotation = if string.len() > MAX_STRING_ANNOTATION {
+        escaped.truncate(MAX_STRING_ANNOTATION);
+        format!("\"{}\"~", String::from_utf8_lossy(&escaped))
+    } else {
+        format!("\"{}\"", String::from_utf8_lossy(&escaped))
+    };
+
+    Some(annotation)
+}
+
+fn number_annotation(
+    prototype: &LuaJitPrototype,
+    instruction: &LuaJitInstruction,
+) -> Option<String> {
+    match instruction.resolve_operand(prototype).ok()?? {
+        OperandValue::Number(constant) => Some(format_number(constant.value())),
+        OperandValue::TableIndex(index) => Some(index.to_string()),
+        _ => None,
+    }
+}
+
+fn function_annotation(
+    bytecode: &DecodedLuaJitBytecode,
+    prototype: &LuaJitPrototype,
+    chunk_name: Option<&str>,
+    instruction: &LuaJitInstruction,
+) -> Option<String> {
+    let child = match instruction.resolve_gc_constant(prototype).ok()?? {
+        ComplexConstantValue::Child(index) => bytecode.prototypes.get(*index as usize)?,
+        _ => return None,
+    };
+
+    Some(location(chunk_name, child.first_line().unwrap_or(0)))
+}
+
+/// Stripped dumps have no names, LuaJIT shows those as empty strings
+fn upvalue_name(prototype: &LuaJitPrototype, index: u32) -> String {
+    usize::try_from(index)
+        .ok()
+        .and_then(|index| prototype.upvalue_names().get(index))
+        .map(|name| name.to_string())
+        .unwrap_or_default()
+}
+
+/// Formats a number like Lua's `tostring`, which uses `%.14g`
+fn format_number(number: f64) -> String {
+    const PRECISION: i32 = 14;
+
+    if number.is_nan() {
+        return "nan".to_owned();
+    }
+    if number.is_infinite() {
+        return if number > 0.0 { "inf" } else { "-inf" }.to_owned();
+    }
+    if number == 0.0 {
+        return if number.is_sign_negative() { "-0" } else { "0" }.to_owned();
+    }
+
+    // Rounding to the precision first gives the exponent `%g` decides with
+    let scientific = format!("{:.*e}", (PRECISION - 1) as usize, number);
+    let (mantissa, exponent) = scientific
+        .split_once('e')
+        .expect("`{:e}` always produces an exponent");
+    let exponent: i32 = exponent.parse().expect("`{:e}` exponent is an integer");
+
+    if !(-4..PRECISION).contains(&exponent) {
+        let sign = if exponent < 0 { '-' } else { '+' };
+        format!(
+            "{}e{sign}{:02}",
+            trim_fraction(mantissa),
+            exponent.unsigned_abs()
+        )
+    } else {
+        let decimals = (PRECISION - 1 - exponent) as usize;
+        trim_fraction(&format!("{number:.decimals$}")).to_owned()
+    }
+}
+
+fn trim_fraction(number: &str) -> &str {
+    if number.contains('.') {
+        number.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        number
+    }
+}
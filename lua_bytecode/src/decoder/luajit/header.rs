@@ -1,14 +1,19 @@
-use super::{read_uleb128, util::read_u8, Error, Result};
-use crate::decoder::util::{self, Endianness};
+use super::{options::DecodeOptions, read_uleb128, util::read_u8, write_uleb128, Error, Result};
+use crate::decoder::{
+    lua_string::LuaString,
+    util::{self, Endianness, Source},
+};
 
 use bitflags::bitflags;
-use std::io::Read;
+use std::io::{Read, Write};
+
+const LUAJIT_MAGIC: &[u8; 3] = b"\x1bLJ";
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
-pub struct LuaJitHeader {
+pub struct LuaJitHeader<'a> {
     pub version: LuaJitVersion,
     pub flags: HeaderFlags,
-    pub chunk_name: Option<String>,
+    pub chunk_name: Option<LuaString<'a>>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
@@ -34,8 +39,8 @@ bitflags! {
     }
 }
 
-impl LuaJitHeader {
-    pub(crate) fn from_read<R: Read>(r: &mut R) -> Result<Self> {
+impl<'a> LuaJitHeader<'a> {
+    pub(crate) fn from_read<S: Source<'a>>(r: &mut S, options: &DecodeOptions) -> Result<Self> {
         Self::check_header(r)?;
 
         let version_raw = read_u8(r)?;
@@ -45,7 +50,7 @@ impl LuaJitHeader {
         let flags =
             HeaderFlags::from_bits(flags_raw).ok_or(Error::LuaJitInvalidHeaderFlags(flags_raw))?;
 
-        let chunk_name = Self::read_name(r, &flags)?;
+        let chunk_name = Self::read_name(r, &flags, options)?;
 
         let header = Self {
             version,
@@ -56,8 +61,50 @@ impl LuaJitHeader {
         Ok(header)
     }
 
+    /// Whether the dump uses two slot call frames (`LJ_FR2`), as 64 bit builds with GC64 do
+    pub fn is_fr2(&self) -> bool {
+        self.flags.contains(HeaderFlags::BCDUMP_F_FR2)
+    }
+
+    /// Slots taken by a called function and its frame link, in front of the arguments
+    ///
+    /// Without FR2 both share a single slot.
+    pub fn call_frame_slots(&self) -> u16 {
+        if self.is_fr2() {
+            2
+        } else {
+            1
+        }
+    }
+
+    /// Copies the borrowed chunk name, detaching the header from the decoded input
+    pub fn into_owned(self) -> LuaJitHeader<'static> {
+        LuaJitHeader {
+            version: self.version,
+            flags: self.flags,
+            chunk_name: self.chunk_name.map(LuaString::into_owned),
+        }
+    }
+
+    pub(crate) fn write_to<W: Write>(&self, w: &mut W) -> Result<()> {
+        w.write_all(LUAJIT_MAGIC)?;
+        util::write_u8(w, u8::from(&self.version))?;
+        write_uleb128(w, self.flags.bits())?;
+
+        // Stripped dumps have no chunk name, others always do, even if it wasn't decoded
+        if !self.flags.contains(HeaderFlags::BCDUMP_F_STRIP) {
+            let chunk_name = self
+                .chunk_name
+                .as_ref()
+                .map_or(&[][..], LuaString::as_bytes);
+            write_uleb128(w, chunk_name.len().try_into()?)?;
+            w.write_all(chunk_name)?;
+        }
+
+        Ok(())
+    }
+
     fn check_header<R: Read>(r: &mut R) -> Result<()> {
-        const LUAJIT_MAGIC: &[u8; 3] = b"\x1bLJ";
         let mut buf = [0u8; 3];
 
         r.read_exact(&mut buf)?;
@@ -69,11 +116,16 @@ impl LuaJitHeader {
         }
     }
 
-    fn read_name<R: Read>(r: &mut R, header_flags: &HeaderFlags) -> Result<Option<String>> {
+    fn read_name<S: Source<'a>>(
+        r: &mut S,
+        header_flags: &HeaderFlags,
+        options: &DecodeOptions,
+    ) -> Result<Option<LuaString<'a>>> {
         if header_flags.contains(HeaderFlags::BCDUMP_F_STRIP) {
             Ok(None)
         } else {
             let len = read_uleb128(r)?;
+            options.check_string_length(len)?;
             let as_string = util::read_string(r, len as usize)?;
             Ok(Some(as_string))
         }
@@ -100,3 +152,12 @@ impl TryFrom<u8> for LuaJitVersion {
         }
     }
 }
+
+impl From<&LuaJitVersion> for u8 {
+    fn from(value: &LuaJitVersion) -> Self {
+        match value {
+            LuaJitVersion::LuaJit2_0 => 1,
+            LuaJitVersion::LuaJit2_1 => 2,
+        }
+    }
+}
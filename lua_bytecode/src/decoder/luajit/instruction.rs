@@ -1,31 +1,73 @@
-use std::io::Read;
-
 use super::{
-    header::{HeaderFlags, LuaJitHeader, LuaJitVersion},
-    opcodes::{LuaJit20Opcode, LuaJit21Opcode, LuaJitOpcode},
-    read_uint, Result,
+    constants::{ComplexConstantValue, LuaJitNumericConstant},
+    header::LuaJitHeader,
+    opcodes::{LuaJitOpcode, Opcode},
+    prototype::LuaJitPrototype,
+    revision::OpcodeTable,
+    Error, Result,
 };
 
-pub enum InstructionPretty {
-    FuncV,
-    FuncF,
-}
+use std::ops::Range;
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
-pub(crate) enum InstructionOperandsFormat {
+/// Layout of the operands, see [`Opcode::format`]
+///
+/// [`Opcode::format`]: super::opcodes::Opcode::format
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum InstructionOperandsFormat {
     Abc,
     Ad,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum InstructionOperands {
-    Abc { a: i8, b: i8, c: i8 },
-    Ad { a: u8, d: u16 },
+    Abc {
+        a: Option<InstructionOperand>,
+        b: Option<InstructionOperand>,
+        c: Option<InstructionOperand>,
+    },
+    Ad {
+        a: Option<InstructionOperand>,
+        d: Option<InstructionOperand>,
+    },
+}
+
+/// An operand decoded according to its [`ArgumentType`]
+///
+/// Constant indices are kept as they are encoded: numbers index
+/// [`LuajitConstants::numeric_constants`] from the start, while strings, tables, functions and
+/// cdata index [`LuajitConstants::complex_constants`] from the end.
+///
+/// [`LuajitConstants::numeric_constants`]: super::constants::LuajitConstants::numeric_constants
+/// [`LuajitConstants::complex_constants`]: super::constants::LuajitConstants::complex_constants
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum InstructionOperand {
+    /// Stack slot (`T_VAR`, `T_DST`, `T_BS`, `T_RBS`)
+    Slot(u16),
+    /// Upvalue index (`T_UV`)
+    Upvalue(u16),
+    /// Unsigned literal (`T_LIT`)
+    Literal(u16),
+    /// Signed literal (`T_SLIT`)
+    SignedLiteral(i16),
+    /// Primitive value (`T_PRI`): `0` is nil, `1` false and `2` true
+    Primitive(u16),
+    /// Numeric constant index (`T_NUM`)
+    Number(u16),
+    /// String constant index (`T_STR`)
+    String(u16),
+    /// Table template constant index (`T_TAB`)
+    Table(u16),
+    /// Function prototype constant index (`T_FUN`)
+    Function(u16),
+    /// Cdata constant index (`T_CDT`)
+    Cdata(u16),
+    /// Absolute pc of the jump target (`T_JMP`)
+    Jump(u32),
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 #[allow(non_camel_case_types)]
-pub(super) enum ArgumentType {
+pub enum ArgumentType {
     T_VAR = 0,
     T_DST = 1,
 
@@ -49,41 +91,50 @@ pub(super) enum ArgumentType {
     //SLOT_TRUE = 30001,
 }
 
+/// Stack slots used by a call, an iterator call or `VARG`, see [`LuaJitInstruction::call_slots`]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CallSlots {
+    /// Slot of the called function, `None` for `VARG`
+    pub function: Option<u16>,
+    /// Slots of the arguments passed in fixed positions, `None` for `VARG`, which passes none
+    pub arguments: Option<Range<u16>>,
+    /// Whether the values left by the previous `MULTRES` instruction are passed after `arguments`
+    pub multres_arguments: bool,
+    /// Slots the results are written to, `None` when all of them are kept or for tail calls, which
+    /// never return to this function
+    pub results: Option<Range<u16>>,
+}
+
+/// Value of a primitive, signed literal or numeric constant operand, see
+/// [`LuaJitInstruction::resolve_operand`]
+#[derive(Clone, Debug, PartialEq)]
+pub enum OperandValue<'p> {
+    Nil,
+    False,
+    True,
+    /// Signed literal (`T_SLIT`) of `KSHORT`
+    Short(i16),
+    Number(&'p LuaJitNumericConstant),
+    /// First array index `TSETM` stores its values at
+    TableIndex(u32),
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct LuaJitInstruction {
     pub opcode: LuaJitOpcode,
     pub operands: InstructionOperands,
     pub arg_count: u8,
 
-    // It's here because it will be needed for enconder
-    // (i will probably remove it in future)
+    /// Code word as read, which [`LuaJitInstruction::raw`] hands back to the encoder
     raw_instruction: u32,
 }
 
 impl LuaJitInstruction {
-    pub(crate) fn from_read<R: Read>(
-        r: &mut R,
-        luajit_header: &LuaJitHeader,
-        //protoype_flags: &PrototypeFlags,
-        complex_constants_count: u32,
-    ) -> Result<Self> {
-        //let header = if protoype_flags.contains(PrototypeFlags::FLAG_IS_VARIADIC) {
-        //    InstructionPretty::FuncV
-        //} else {
-        //    InstructionPretty::FuncF
-        //};
-
-        let code_word = read_uint(r, luajit_header.flags.contains(HeaderFlags::BCDUMP_F_BE))?;
-
+    pub(crate) fn decode(code_word: u32, opcodes: &OpcodeTable, pc: u32) -> Result<Self> {
         let opcode_raw = code_word & 0xff;
+        let opcode = opcodes.decode(opcode_raw)?;
 
-        let opcode = match luajit_header.version {
-            LuaJitVersion::LuaJit2_0 => LuaJitOpcode::Lj20(LuaJit20Opcode::try_from(opcode_raw)?),
-            LuaJitVersion::LuaJit2_1 => LuaJitOpcode::Lj21(LuaJit21Opcode::try_from(opcode_raw)?),
-        };
-
-        let (operands, arg_count) =
-            get_instruction_opeands_v2(code_word, complex_constants_count, &opcode)?;
+        let (operands, arg_count) = decode_operands(code_word, pc, &opcode)?;
 
         let instruction = Self {
             opcode,
@@ -94,55 +145,197 @@ impl LuaJitInstruction {
         };
         Ok(instruction)
     }
+
+    /// Placeholder for a word that couldn't be decoded, see [`LuaJitOpcode::Unknown`]
+    pub(crate) fn unknown(code_word: u32) -> Self {
+        Self {
+            opcode: LuaJitOpcode::Unknown(code_word as u8),
+            operands: InstructionOperands::Ad { a: None, d: None },
+            arg_count: 0,
+            raw_instruction: code_word,
+        }
+    }
+
+    /// The instruction word as it was dumped
+    pub fn raw(&self) -> u32 {
+        self.raw_instruction
+    }
+
+    /// Absolute pc the `T_JMP` operand of `JMP`, `FORI`, `FORL`, `ITERL`, `LOOP`, `UCLO` or
+    /// `ISNEXT` leads to
+    ///
+    /// The `I` variants jump the same way, the `J` ones hold a trace number instead. Comparisons
+    /// and tests branch through the `JMP` following them, see [`LuaJitPrototype::branch_target`].
+    pub fn jump_target(&self) -> Option<u32> {
+        match self.last_operand() {
+            Some(InstructionOperand::Jump(target)) => Some(target),
+            _ => None,
+        }
+    }
+
+    /// Looks up the value of a primitive (`T_PRI`), signed literal (`T_SLIT`) or numeric constant
+    /// (`T_NUM`) operand in `prototype`, the prototype the instruction belongs to
+    ///
+    /// `None` if the instruction has no such operand.
+    pub fn resolve_operand<'p>(
+        &self,
+        prototype: &'p LuaJitPrototype,
+    ) -> Result<Option<OperandValue<'p>>> {
+        let value = match self.last_operand() {
+            Some(InstructionOperand::Primitive(0)) => OperandValue::Nil,
+            Some(InstructionOperand::Primitive(1)) => OperandValue::False,
+            Some(InstructionOperand::Primitive(2)) => OperandValue::True,
+            Some(InstructionOperand::Primitive(primitive)) => {
+                return Err(Error::LuaJitInvalidPrimitive(primitive))
+            }
+            Some(InstructionOperand::SignedLiteral(literal)) => OperandValue::Short(literal),
+            Some(InstructionOperand::Number(index)) => {
+                let numeric_constants = &prototype.constants().numeric_constants;
+                let constant = numeric_constants.get(usize::from(index)).ok_or(
+                    Error::LuaJitNumericConstantOutOfRange {
+                        index,
+                        count: numeric_constants.len(),
+                    },
+                )?;
+
+                match self.opcode.canonical() {
+                    // The index is kept in the low bits of `2^52 + index`, which the VM reads as is
+                    Opcode::TSETM => OperandValue::TableIndex(constant.value().to_bits() as u32),
+                    _ => OperandValue::Number(constant),
+                }
+            }
+            _ => return Ok(None),
+        };
+
+        Ok(Some(value))
+    }
+
+    /// Looks up the string, table, function or cdata constant an operand refers to in
+    /// `prototype`, the prototype the instruction belongs to
+    ///
+    /// Those operands index the pool of GC constants from its end. `None` if the instruction has
+    /// no such operand, e.g. anything but `GGET`, `GSET`, `TGETS`, `TSETS`, `KSTR`, `USETS`,
+    /// `ISEQS`, `ISNES`, `TDUP`, `FNEW` and `KCDATA`. Fails if the constant isn't of the kind the
+    /// operand asks for, e.g. a string where `FNEW` expects a function.
+    pub fn resolve_gc_constant<'p, 'a>(
+        &self,
+        prototype: &'p LuaJitPrototype<'a>,
+    ) -> Result<Option<&'p ComplexConstantValue<'a>>> {
+        let operand = self.last_operand();
+        let (index, expected) = match operand {
+            Some(InstructionOperand::String(index)) => (index, "string"),
+            Some(InstructionOperand::Table(index)) => (index, "table"),
+            Some(InstructionOperand::Function(index)) => (index, "function"),
+            Some(InstructionOperand::Cdata(index)) => (index, "cdata"),
+            _ => return Ok(None),
+        };
+
+        let constants = prototype.constants();
+        let constant = constants
+            .gc_constant(index)
+            .ok_or(Error::LuaJitGcConstantOutOfRange {
+                index,
+                count: constants.complex_constants.len(),
+            })?;
+
+        let is_expected = match (operand, constant) {
+            (Some(InstructionOperand::String(_)), ComplexConstantValue::String(_))
+            | (Some(InstructionOperand::Table(_)), ComplexConstantValue::Table(_))
+            | (Some(InstructionOperand::Function(_)), ComplexConstantValue::Child(_)) => true,
+            (Some(InstructionOperand::Cdata(_)), constant) => constant.is_cdata(),
+            _ => false,
+        };
+        if !is_expected {
+            return Err(Error::LuaJitGcConstantKindMismatch { index, expected });
+        }
+
+        Ok(Some(constant))
+    }
+
+    /// Constants are only ever referenced by the last operand
+    fn last_operand(&self) -> Option<InstructionOperand> {
+        match self.operands {
+            InstructionOperands::Abc { c, .. } => c,
+            InstructionOperands::Ad { d, .. } => d,
+        }
+    }
+
+    /// Slots of the function, arguments and results of `CALL`, `CALLM`, `CALLT`, `CALLMT`,
+    /// `ITERC`, `ITERN` and `VARG`, `None` for any other instruction
+    ///
+    /// Arguments start right after the frame of the called function, which is a slot further with
+    /// FR2, see [`LuaJitHeader::call_frame_slots`]. `VARG` only has results, its operands mean the
+    /// same either way.
+    pub fn call_slots(&self, header: &LuaJitHeader) -> Option<CallSlots> {
+        let raw = self.raw_instruction;
+        let base = ((raw >> 8) & 0xff) as u16;
+        let b = (raw >> 24) as u16;
+        let c = ((raw >> 16) & 0xff) as u16;
+        let d = (raw >> 16) as u16;
+
+        let first_argument = base + header.call_frame_slots();
+        // `B` is the number of results plus one, `0` keeps them all
+        let results = (b != 0).then(|| base..base + b - 1);
+        let call = |fixed_arguments: u16, multres_arguments, results| CallSlots {
+            function: Some(base),
+            // `D` of the tail calls spans 16 bits, so malformed ones could go past the stack
+            arguments: Some(first_argument..first_argument.saturating_add(fixed_arguments)),
+            multres_arguments,
+            results,
+        };
+
+        let slots = match self.opcode.canonical() {
+            // `C` is the number of arguments plus one, the iterator calls always pass two
+            Opcode::CALL | Opcode::ITERC | Opcode::ITERN => {
+                call(c.saturating_sub(1), false, results)
+            }
+            Opcode::CALLM => call(c, true, results),
+            Opcode::CALLT => call(d.saturating_sub(1), false, None),
+            Opcode::CALLMT => call(d, true, None),
+            Opcode::VARG => CallSlots {
+                function: None,
+                arguments: None,
+                multres_arguments: false,
+                results,
+            },
+            _ => return None,
+        };
+
+        Some(slots)
+    }
 }
 
-// FIXME: this function is a mess. Sometimes it decodes correctly, sometimes it don't
-fn get_instruction_opeands_v2(
+fn decode_operands(
     code_word: u32,
-    complex_constants_count: u32,
+    pc: u32,
     opcode: &LuaJitOpcode,
 ) -> Result<(InstructionOperands, u8)> {
     let args: [Option<ArgumentType>; 3] = opcode.into();
-    let args_count = args.clone().into_iter().filter(Option::is_some).count();
-    let [a_ty, b_ty, c_ty] = args;
+    let args_count = args.iter().filter(|arg| arg.is_some()).count();
+    let [a_ty, b_ty, cd_ty] = args;
+
+    let a = (code_word >> 8) & 0xff;
+    let a = a_ty.map(|ty| decode_operand(ty, a, pc)).transpose()?;
 
     let instruction_operands_format = InstructionOperandsFormat::from(opcode);
 
     let operands = match instruction_operands_format {
         InstructionOperandsFormat::Abc => {
-            let mut a = (code_word >> 8) & 0xff;
-            let mut c = (code_word >> 16) & 0xff;
-            let mut b = (code_word >> 24) & 0xff;
-
-            if let Some(a_type) = a_ty {
-                a = process_operand(a_type, a, complex_constants_count);
-            }
-            if let Some(b_type) = b_ty {
-                b = process_operand(b_type, b, complex_constants_count);
-            }
-            if let Some(c_type) = c_ty {
-                c = process_operand(c_type, c, complex_constants_count);
-            }
+            let c = (code_word >> 16) & 0xff;
+            let b = (code_word >> 24) & 0xff;
 
             InstructionOperands::Abc {
-                a: a.try_into()?,
-                b: b.try_into()?,
-                c: c.try_into()?,
+                a,
+                b: b_ty.map(|ty| decode_operand(ty, b, pc)).transpose()?,
+                c: cd_ty.map(|ty| decode_operand(ty, c, pc)).transpose()?,
             }
         }
         InstructionOperandsFormat::Ad => {
-            let mut a = (code_word >> 8) & 0xff;
-            let mut d = (code_word >> 16) & 0xffff;
+            let d = (code_word >> 16) & 0xffff;
 
-            if let Some(a_type) = a_ty {
-                a = process_operand(a_type, a, complex_constants_count);
-            }
-            if let Some(c_type) = c_ty {
-                d = process_operand(c_type, d, complex_constants_count);
-            }
             InstructionOperands::Ad {
-                d: d.try_into()?,
-                a: a.try_into()?,
+                a,
+                d: cd_ty.map(|ty| decode_operand(ty, d, pc)).transpose()?,
             }
         }
     };
@@ -150,12 +343,32 @@ fn get_instruction_opeands_v2(
     Ok((operands, args_count.try_into()?))
 }
 
-fn process_operand(op_type: ArgumentType, operand: u32, complex_constants_count: u32) -> u32 {
-    match op_type {
-        ArgumentType::T_STR | ArgumentType::T_TAB | ArgumentType::T_FUN | ArgumentType::T_CDT => {
-            complex_constants_count - operand - 1
+fn decode_operand(op_type: ArgumentType, operand: u32, pc: u32) -> Result<InstructionOperand> {
+    let operand = u16::try_from(operand)?;
+
+    let decoded = match op_type {
+        ArgumentType::T_VAR | ArgumentType::T_DST | ArgumentType::T_BS | ArgumentType::T_RBS => {
+            InstructionOperand::Slot(operand)
         }
-        ArgumentType::T_JMP => operand - 0x8000,
-        _ => operand,
-    }
+        ArgumentType::T_UV => InstructionOperand::Upvalue(operand),
+        ArgumentType::T_LIT => InstructionOperand::Literal(operand),
+        ArgumentType::T_SLIT => InstructionOperand::SignedLiteral(operand as i16),
+        ArgumentType::T_PRI => InstructionOperand::Primitive(operand),
+        ArgumentType::T_NUM => InstructionOperand::Number(operand),
+        ArgumentType::T_STR => InstructionOperand::String(operand),
+        ArgumentType::T_TAB => InstructionOperand::Table(operand),
+        ArgumentType::T_FUN => InstructionOperand::Function(operand),
+        ArgumentType::T_CDT => InstructionOperand::Cdata(operand),
+        ArgumentType::T_JMP => InstructionOperand::Jump(jump_target(pc, operand)?),
+    };
+
+    Ok(decoded)
+}
+
+/// Jumps are relative to the next instruction and biased by `0x8000`
+fn jump_target(pc: u32, operand: u16) -> Result<u32> {
+    const JUMP_BIAS: i64 = 0x8000;
+
+    let target = i64::from(pc) + 1 + i64::from(operand) - JUMP_BIAS;
+    u32::try_from(target).map_err(|_| Error::LuaJitInvalidJumpTarget { pc, operand })
 }
@@ -0,0 +1,46 @@
+//! Names for the jump targets of a prototype
+
+use super::instruction::LuaJitInstruction;
+
+/// The pcs a prototype jumps to, numbered in pc order
+///
+/// Shared by the disassembler, which marks them with `=>`, and whatever splits the instructions
+/// into basic blocks. Comparisons and tests jump through the `JMP` following them, so their
+/// targets are in there as well.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct LabelTable {
+    /// Sorted and without duplicates
+    targets: Vec<u32>,
+}
+
+impl LabelTable {
+    pub fn new(instructions: &[LuaJitInstruction]) -> Self {
+        let mut targets: Vec<_> = instructions
+            .iter()
+            .filter_map(LuaJitInstruction::jump_target)
+            .collect();
+        targets.sort_unstable();
+        targets.dedup();
+
+        Self { targets }
+    }
+
+    /// Every jump target, in increasing order
+    pub fn targets(&self) -> &[u32] {
+        &self.targets
+    }
+
+    pub fn is_target(&self, pc: u32) -> bool {
+        self.index(pc).is_some()
+    }
+
+    /// Position of `pc` among the targets, `None` if nothing jumps there
+    pub fn index(&self, pc: u32) -> Option<usize> {
+        self.targets.binary_search(&pc).ok()
+    }
+
+    /// Name of the label at `pc`, `L1` being the first target of the prototype
+    pub fn name(&self, pc: u32) -> Option<String> {
+        self.index(pc).map(|index| format!("L{}", index + 1))
+    }
+}
@@ -1,46 +1,270 @@
 use super::{
-    error::{Error, Result},
-    util::{self, read_u8},
+    error::{DecodeLimit, DecodeSection, Diagnostic, Error, Result},
+    util::{self, read_u8, CountingReader, SliceReader, Source},
 };
 use header::LuaJitHeader;
+use instruction::LuaJitInstruction;
+use normalise::UnnormalisedInstruction;
+use options::DecodeOptions;
 use prototype::LuaJitPrototype;
-use std::io::Read;
+use std::io::{Read, Take, Write};
 
 pub mod constants;
 pub mod debuginfo;
+pub mod disassembler;
 pub mod header;
 pub mod instruction;
+pub mod labels;
+pub mod normalise;
 pub mod opcodes;
+pub mod options;
 pub mod prototype;
+pub mod revision;
+pub mod skim;
+pub mod stream;
 pub mod table;
 
+/// A decoded dump
+///
+/// Decoding from a slice with [`DecodedLuaJitBytecode::from_slice`] borrows strings from the slice
+/// instead of copying them, [`DecodedLuaJitBytecode::into_owned`] detaches the result from it.
 #[derive(Clone, Debug, PartialEq)]
-pub struct DecodedLuaJitBytecode {
-    pub header: LuaJitHeader,
-    pub prototypes: Vec<LuaJitPrototype>,
+pub struct DecodedLuaJitBytecode<'a> {
+    pub header: LuaJitHeader<'a>,
+    pub prototypes: Vec<LuaJitPrototype<'a>>,
 }
 
-impl DecodedLuaJitBytecode {
-    pub fn from_read<R: Read>(mut r: R) -> Result<Self> {
-        let header = LuaJitHeader::from_read(&mut r)?;
+impl DecodedLuaJitBytecode<'static> {
+    pub fn from_read<R: Read>(r: R) -> Result<Self> {
+        Self::from_read_with_options(r, &DecodeOptions::default())
+    }
 
-        let mut prototypes = vec![];
+    /// Same as [`Self::from_read`], but fails with [`Error::LuaJitLimitExceeded`] once the dump
+    /// goes over one of the `options`
+    pub fn from_read_with_options<R: Read>(r: R, options: &DecodeOptions) -> Result<Self> {
+        let mut r = size_limited(r, options);
+        let decoded = Self::decode(&mut r, options);
+        enforce_file_size(decoded, &r, options)
+    }
+}
 
-        let mut prototype_count = 0;
-        loop {
-            match LuaJitPrototype::from_read(&mut r, &header, &mut prototype_count) {
-                Ok(prototype_option) => match prototype_option {
-                    Some(prototype) => prototypes.push(prototype),
-                    None => break,
-                },
-                Err(e) => return Err(e),
-            }
+impl<'a> DecodedLuaJitBytecode<'a> {
+    /// Decodes a dump held in memory, borrowing its strings rather than copying them
+    pub fn from_slice(data: &'a [u8]) -> Result<Self> {
+        Self::from_slice_with_options(data, &DecodeOptions::default())
+    }
+
+    /// Same as [`Self::from_slice`], but fails with [`Error::LuaJitLimitExceeded`] once the dump
+    /// goes over one of the `options`
+    pub fn from_slice_with_options(data: &'a [u8], options: &DecodeOptions) -> Result<Self> {
+        options
+            .check_file_size(data.len().try_into()?)
+            .map_err(|e| e.at_offset(0, None))?;
+
+        Self::decode(&mut SliceReader::new(data), options)
+    }
+
+    /// Copies everything borrowed from the decoded input
+    pub fn into_owned(self) -> DecodedLuaJitBytecode<'static> {
+        DecodedLuaJitBytecode {
+            header: self.header.into_owned(),
+            prototypes: self
+                .prototypes
+                .into_iter()
+                .map(LuaJitPrototype::into_owned)
+                .collect(),
+        }
+    }
+
+    fn decode<S: Source<'a>>(r: &mut S, options: &DecodeOptions) -> Result<Self> {
+        let header = LuaJitHeader::from_read(r, options).map_err(|e| {
+            e.in_section(DecodeSection::Header)
+                .at_offset(r.position(), None)
+        })?;
+
+        let mut prototypes = vec![];
+        let mut decoder = PrototypeDecoder::default();
+        while let Some(prototype) = decoder.next(r, &header, options)? {
+            prototypes.push(prototype);
         }
 
         let decoded = Self { header, prototypes };
 
         Ok(decoded)
     }
+
+    /// Serialises the bytecode back into the `\x1bLJ` dump format
+    ///
+    /// Decoding a well-formed dump and writing it back produces identical bytes. Setting
+    /// [`HeaderFlags::BCDUMP_F_STRIP`] beforehand strips the dump: the chunk name and debug info
+    /// are left out.
+    ///
+    /// [`HeaderFlags::BCDUMP_F_STRIP`]: header::HeaderFlags::BCDUMP_F_STRIP
+    pub fn write_to<W: Write>(&self, mut w: W) -> Result<()> {
+        self.header.write_to(&mut w)?;
+
+        for prototype in &self.prototypes {
+            prototype.write_to(&mut w, &self.header)?;
+        }
+
+        // A zero sized prototype terminates the dump
+        write_uleb128(&mut w, 0)
+    }
+
+    /// Every problem skipped over by a lenient decode, see [`DecodeOptions::lenient`]
+    pub fn diagnostics(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.prototypes
+            .iter()
+            .flat_map(LuaJitPrototype::diagnostics)
+    }
+
+    /// Renders the bytecode as `luajit -bl` lists it
+    pub fn disassemble(&self) -> String {
+        let mut listing = String::new();
+        disassembler::write_listing(self, &mut listing)
+            .expect("formatting into a `String` doesn't fail");
+        listing
+    }
+
+    /// The main chunk, which is always dumped last
+    pub fn root(&self) -> Option<&LuaJitPrototype<'a>> {
+        self.prototypes.last()
+    }
+
+    /// Nested functions of the prototype at `index`, in the order they are defined in the source
+    pub fn children(&self, index: usize) -> impl Iterator<Item = &LuaJitPrototype<'a>> {
+        self.prototypes
+            .get(index)
+            .into_iter()
+            .flat_map(LuaJitPrototype::child_indices)
+            .filter_map(|child| self.prototypes.get(child))
+    }
+
+    /// Index of the prototype that defines the prototype at `index`, `None` for the main chunk
+    pub fn parent(&self, index: usize) -> Option<usize> {
+        // Parents are always dumped after their children
+        self.prototypes
+            .iter()
+            .enumerate()
+            .skip(index + 1)
+            .find(|(_, prototype)| prototype.child_indices().any(|child| child == index))
+            .map(|(parent, _)| parent)
+    }
+
+    /// Rewrites the instructions patched by the JIT compiler back to the ones it started from, see
+    /// [`normalise`]
+    ///
+    /// `IFORL` becomes `FORL` again, `JFORL` becomes `FORL` with its jump worked out from the
+    /// matching `FORI`, and so on. Returns the instructions that couldn't be rewritten, which are
+    /// left as they are.
+    pub fn normalise(&mut self) -> Vec<UnnormalisedInstruction> {
+        self.normalise_with_options(&DecodeOptions::default())
+    }
+
+    /// Same as [`Self::normalise`], with the base opcodes numbered as [`DecodeOptions::opcodes`]
+    /// says
+    pub fn normalise_with_options(
+        &mut self,
+        options: &DecodeOptions,
+    ) -> Vec<UnnormalisedInstruction> {
+        let opcodes = options.opcode_table(&self.header);
+
+        let mut failed = vec![];
+        for (index, prototype) in self.prototypes.iter_mut().enumerate() {
+            for pc in normalise::normalise_prototype(prototype, opcodes) {
+                failed.push(UnnormalisedInstruction {
+                    prototype: index,
+                    pc,
+                    opcode: prototype.instructions()[pc as usize - 1].opcode.canonical(),
+                });
+            }
+        }
+
+        failed
+    }
+
+    /// Resolves the function created by an `FNEW` instruction of `prototype`
+    pub fn resolve_fnew(
+        &self,
+        prototype: &LuaJitPrototype,
+        instruction: &LuaJitInstruction,
+    ) -> Option<&LuaJitPrototype<'a>> {
+        prototype
+            .fnew_child_index(instruction)
+            .and_then(|index| self.prototypes.get(index))
+    }
+}
+
+/// Stops reading right after the input went over [`DecodeOptions::max_file_size`]
+pub(crate) fn size_limited<R: Read>(r: R, options: &DecodeOptions) -> CountingReader<Take<R>> {
+    // Reading a single byte past the limit is enough to tell it was exceeded
+    let size_limit = options
+        .max_file_size
+        .map_or(u64::MAX, |max| max.saturating_add(1));
+    CountingReader::new(r.take(size_limit))
+}
+
+/// Replaces the outcome of reading from a [`size_limited`] input if it went over the limit
+pub(crate) fn enforce_file_size<T, R: Read>(
+    result: Result<T>,
+    r: &CountingReader<R>,
+    options: &DecodeOptions,
+) -> Result<T> {
+    // Running out of allowed input shows up as a truncated dump, so the limit takes precedence
+    match options.check_file_size(r.position()) {
+        Ok(()) => result,
+        Err(error) => Err(error.at_offset(r.position(), None)),
+    }
+}
+
+/// Bookkeeping shared by every way of decoding the prototypes following the header
+#[derive(Default)]
+pub(crate) struct PrototypeDecoder {
+    // Mirrors the stack LuaJIT keeps while loading: every decoded prototype is pushed and later
+    // popped by the `KGC_CHILD` constant of its parent
+    child_stack: Vec<u32>,
+    /// Nesting depth of the functions inside each prototype, `0` if it has no children
+    depths: Vec<usize>,
+}
+
+impl PrototypeDecoder {
+    /// Decodes the next prototype, `None` once the terminator is reached
+    pub(crate) fn next<'a, S: Source<'a>>(
+        &mut self,
+        r: &mut S,
+        header: &LuaJitHeader,
+        options: &DecodeOptions,
+    ) -> Result<Option<LuaJitPrototype<'a>>> {
+        let index = self.depths.len();
+        let prototype =
+            LuaJitPrototype::from_read(r, header, &mut self.child_stack, index, options)
+                .map_err(|e| e.at_offset(r.position(), Some(index)))?;
+
+        let Some(prototype) = prototype else {
+            // Only the main chunk may be left unclaimed, unless damaged parents lost track of
+            // their children
+            if self.child_stack.len() > 1 && !options.lenient {
+                let error = Error::LuaJitDanglingPrototypes(self.child_stack.len() - 1);
+                return Err(error.at_offset(r.position(), None));
+            }
+            return Ok(None);
+        };
+
+        let depth = prototype
+            .child_indices()
+            .map(|child| self.depths[child] + 1)
+            .max()
+            .unwrap_or(0);
+        options
+            .check_prototypes(index + 1)
+            .and_then(|()| options.check_nesting_depth(depth))
+            .map_err(|e| e.at_offset(r.position(), Some(index)))?;
+
+        self.child_stack.push(index.try_into()?);
+        self.depths.push(depth);
+
+        Ok(Some(prototype))
+    }
 }
 
 pub(super) fn read_uleb128<R: Read>(r: &mut R) -> Result<u32> {
@@ -54,6 +278,9 @@ pub(super) fn read_uleb128<R: Read>(r: &mut R) -> Result<u32> {
             let byte: u32 = util::read_u8(r)?.into();
 
             bit_shift += 7;
+            if bit_shift >= u32::BITS {
+                return Err(Error::LuaJitUleb128Overflow);
+            }
             value |= (byte & 0x7f) << bit_shift;
 
             if byte < 0x80 {
@@ -64,6 +291,14 @@ pub(super) fn read_uleb128<R: Read>(r: &mut R) -> Result<u32> {
     Ok(value)
 }
 
+pub(super) fn write_uleb128<W: Write>(w: &mut W, mut value: u32) -> Result<()> {
+    while value >= 0x80 {
+        util::write_u8(w, (value & 0x7f) as u8 | 0x80)?;
+        value >>= 7;
+    }
+    util::write_u8(w, value as u8)
+}
+
 pub(super) fn read_uint<R: Read>(r: &mut R, is_big_endian: bool) -> Result<u32> {
     let mut buf = [0u8; 4];
     r.read_exact(&mut buf)?;
@@ -74,6 +309,15 @@ pub(super) fn read_uint<R: Read>(r: &mut R, is_big_endian: bool) -> Result<u32>
     }
 }
 
+pub(super) fn write_uint<W: Write>(w: &mut W, value: u32, is_big_endian: bool) -> Result<()> {
+    if is_big_endian {
+        w.write_all(&value.to_be_bytes())?;
+    } else {
+        w.write_all(&value.to_le_bytes())?;
+    }
+    Ok(())
+}
+
 fn get_uleb128_33<R: Read>(r: &mut R) -> Result<(bool, u32)> {
     let first_byte: u32 = read_u8(r)?.into();
 
@@ -81,14 +325,18 @@ fn get_uleb128_33<R: Read>(r: &mut R) -> Result<(bool, u32)> {
     let mut value: u32 = first_byte >> 1u32;
 
     if value >= 0x40 {
-        let mut bit_shift: i8 = -1;
+        // The first byte only holds 6 bits of the value
+        let mut bit_shift = 6;
         value &= 0x3F;
 
         loop {
             let byte: u32 = read_u8(r)?.into();
 
-            bit_shift += 7;
+            if bit_shift >= u32::BITS {
+                return Err(Error::LuaJitUleb128Overflow);
+            }
             value |= (byte & 0x7F) << bit_shift;
+            bit_shift += 7;
             if byte < 0x80 {
                 break;
             }
@@ -97,3 +345,13 @@ fn get_uleb128_33<R: Read>(r: &mut R) -> Result<(bool, u32)> {
 
     Ok((is_number_bit != 0, value))
 }
+
+/// Counterpart of [`get_uleb128_33`]: the lowest bit of the first byte tells whether a number follows
+fn write_uleb128_33<W: Write>(w: &mut W, is_number: bool, value: u32) -> Result<()> {
+    let mut value = u64::from(value) << 1 | u64::from(is_number);
+    while value >= 0x80 {
+        util::write_u8(w, (value & 0x7f) as u8 | 0x80)?;
+        value >>= 7;
+    }
+    util::write_u8(w, value as u8)
+}
@@ -0,0 +1,151 @@
+//! Undoing the bytecode patches of the JIT compiler
+//!
+//! LuaJIT rewrites the bytecode of running functions: hot loops get their loop instruction swapped
+//! for a `J`-prefixed one holding a trace number instead of a jump, blacklisted ones for an
+//! `I`-prefixed one. `string.dump` undoes this, but bytecode read out of a live process or through
+//! `jit.util.funcbc` still has them.
+
+use super::{
+    instruction::LuaJitInstruction,
+    opcodes::{Opcode, OpcodeVariant},
+    prototype::LuaJitPrototype,
+    revision::OpcodeTable,
+};
+
+/// An instruction [`DecodedLuaJitBytecode::normalise`] had to leave patched
+///
+/// [`DecodedLuaJitBytecode::normalise`]: super::DecodedLuaJitBytecode::normalise
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct UnnormalisedInstruction {
+    /// Index of the prototype in dump order
+    pub prototype: usize,
+    pub pc: u32,
+    pub opcode: Opcode,
+}
+
+/// Rewrites the patched instructions of `prototype`, returning the pcs of those it couldn't
+pub(crate) fn normalise_prototype(
+    prototype: &mut LuaJitPrototype,
+    opcodes: &OpcodeTable,
+) -> Vec<u32> {
+    // Every target is worked out before anything is rewritten, from the instructions as decoded
+    let unpatched: Vec<_> = (1..)
+        .zip(prototype.instructions())
+        .filter(|(_, instruction)| instruction.opcode.canonical().variant() != OpcodeVariant::Base)
+        .map(|(pc, instruction)| {
+            let code_word = unpatch(prototype.instructions(), pc, instruction, opcodes);
+            let instruction = code_word
+                .and_then(|code_word| LuaJitInstruction::decode(code_word, opcodes, pc).ok());
+            (pc, instruction)
+        })
+        .collect();
+
+    let mut failed = vec![];
+    for (pc, instruction) in unpatched {
+        match instruction {
+            Some(instruction) => prototype.instructions_mut()[pc as usize - 1] = instruction,
+            None => failed.push(pc),
+        }
+    }
+
+    failed
+}
+
+/// The code word the instruction at `pc` had before being patched, `None` if it can't be told
+fn unpatch(
+    instructions: &[LuaJitInstruction],
+    pc: u32,
+    instruction: &LuaJitInstruction,
+    opcodes: &OpcodeTable,
+) -> Option<u32> {
+    let raw = instruction.raw();
+    let a = (raw >> 8) & 0xff;
+    let opcode = instruction.opcode.canonical();
+
+    let d = match opcode {
+        // Only the opcode was swapped, `JFORI` still holds its jump
+        Opcode::JFORI | Opcode::IFORL | Opcode::IITERL | Opcode::ILOOP => raw >> 16,
+        Opcode::IFUNCF | Opcode::IFUNCV => raw >> 16,
+        // The function headers have no `D`
+        Opcode::JFUNCF | Opcode::JFUNCV => 0,
+
+        // `FORI` jumps right past its `FORL`, which jumps back right past the `FORI`
+        Opcode::JFORL => {
+            let fori = instruction_pcs(instructions).find(|&(_, other)| {
+                other.opcode.canonical().base() == Opcode::FORI
+                    && slot_a(other) == a
+                    && other.jump_target() == Some(pc + 1)
+            })?;
+            jump_operand(pc, fori.0 + 1)?
+        }
+
+        // `ITERL` follows the `ITERC` or `ITERN` the loop is entered by, and jumps back right
+        // past the `JMP` or `ISNEXT` that entered it
+        Opcode::JITERL => {
+            let call_pc = pc.checked_sub(1)?;
+            let call = instructions.get(call_pc.checked_sub(1)? as usize)?;
+            if !matches!(call.opcode.canonical(), Opcode::ITERC | Opcode::ITERN) {
+                return None;
+            }
+
+            let entry = instruction_pcs(instructions).find(|&(_, other)| {
+                matches!(other.opcode.canonical(), Opcode::JMP | Opcode::ISNEXT)
+                    && other.jump_target() == Some(call_pc)
+            })?;
+            jump_operand(pc, entry.0 + 1)?
+        }
+
+        Opcode::JLOOP => jump_operand(pc, loop_exit(instructions, pc)?)?,
+
+        _ => return None,
+    };
+
+    let number = opcodes.canonical_number(opcode.base())?;
+    Some(number | a << 8 | d << 16)
+}
+
+/// Where the loop of the `JLOOP` at `pc` exits to, if it replaced a `LOOP`
+///
+/// `JLOOP` also replaces `ITERN` and the returns whole, whose operands are lost. It is only taken
+/// for a `LOOP` when a loop is laid out around it.
+fn loop_exit(instructions: &[LuaJitInstruction], pc: u32) -> Option<u32> {
+    let at = |pc: u32| instructions.get(pc.checked_sub(1)? as usize);
+
+    if at(pc + 1).is_some_and(|next| next.opcode.canonical().base() == Opcode::ITERL) {
+        return None;
+    }
+
+    // `repeat` and unconditional `while` loops start at `LOOP`, the last jump back to it closes
+    // them, the earlier ones come from conditions at the end of the body
+    let closing = instruction_pcs(instructions)
+        .skip(pc as usize)
+        .filter(|&(_, other)| other.jump_target() == Some(pc))
+        .last();
+    if let Some((closing_pc, _)) = closing {
+        return Some(closing_pc + 1);
+    }
+
+    // Other `while` loops check their condition right before `LOOP`, jumping to the exit when it
+    // fails, and close by jumping back to that condition right before the exit
+    let previous = at(pc - 1).filter(|previous| previous.opcode.canonical() == Opcode::JMP)?;
+    let exit = previous.jump_target().filter(|&exit| exit > pc)?;
+    let start = at(exit - 1)?.jump_target()?;
+
+    (start < pc).then_some(exit)
+}
+
+fn instruction_pcs(
+    instructions: &[LuaJitInstruction],
+) -> impl Iterator<Item = (u32, &LuaJitInstruction)> {
+    (1..).zip(instructions)
+}
+
+fn slot_a(instruction: &LuaJitInstruction) -> u32 {
+    (instruction.raw() >> 8) & 0xff
+}
+
+/// Encodes the jump from `pc` to `target`, see [`InstructionOperand::Jump`]
+fn jump_operand(pc: u32, target: u32) -> Option<u32> {
+    let operand = i64::from(target) - i64::from(pc) - 1 + 0x8000;
+    u16::try_from(operand).ok().map(u32::from)
+}
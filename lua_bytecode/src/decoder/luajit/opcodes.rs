@@ -1,12 +1,19 @@
-use super::{
-    instruction::{ArgumentType, InstructionOperandsFormat},
-    Error, Result,
-};
+use super::instruction::{ArgumentType, InstructionOperandsFormat};
+
+use std::fmt;
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum LuaJitOpcode {
     Lj20(LuaJit20Opcode),
     Lj21(LuaJit21Opcode),
+    /// Stands in for an instruction word lenient decoding couldn't make sense of, holding its
+    /// opcode number
+    ///
+    /// The word itself is kept in [`LuaJitInstruction::raw`], and the reason in the diagnostics of
+    /// the prototype.
+    ///
+    /// [`LuaJitInstruction::raw`]: super::instruction::LuaJitInstruction::raw
+    Unknown(u8),
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
@@ -243,245 +250,157 @@ pub enum LuaJit21Opcode {
     FUNCCW,
 }
 
-impl TryFrom<u32> for LuaJit21Opcode {
-    type Error = Error;
-
-    fn try_from(value: u32) -> Result<Self> {
-        match value {
-            0 => Ok(Self::ISLT),
-            1 => Ok(Self::ISGE),
-            2 => Ok(Self::ISLE),
-            3 => Ok(Self::ISGT),
-            4 => Ok(Self::ISEQV),
-            5 => Ok(Self::ISNEV),
-            6 => Ok(Self::ISEQS),
-            7 => Ok(Self::ISNES),
-            8 => Ok(Self::ISEQN),
-            9 => Ok(Self::ISNEN),
-            10 => Ok(Self::ISEQP),
-            11 => Ok(Self::ISNEP),
-            12 => Ok(Self::ISTC),
-            13 => Ok(Self::ISFC),
-            14 => Ok(Self::IST),
-            15 => Ok(Self::ISF),
-            16 => Ok(Self::ISTYPE),
-            17 => Ok(Self::ISNUM),
-            18 => Ok(Self::MOV),
-            19 => Ok(Self::NOT),
-            20 => Ok(Self::UNM),
-            21 => Ok(Self::LEN),
-            22 => Ok(Self::ADDVN),
-            23 => Ok(Self::SUBVN),
-            24 => Ok(Self::MULVN),
-            25 => Ok(Self::DIVVN),
-            26 => Ok(Self::MODVN),
-            27 => Ok(Self::ADDNV),
-            28 => Ok(Self::SUBNV),
-            29 => Ok(Self::MULNV),
-            30 => Ok(Self::DIVNV),
-            31 => Ok(Self::MODNV),
-            32 => Ok(Self::ADDVV),
-            33 => Ok(Self::SUBVV),
-            34 => Ok(Self::MULVV),
-            35 => Ok(Self::DIVVV),
-            36 => Ok(Self::MODVV),
-            37 => Ok(Self::POW),
-            38 => Ok(Self::CAT),
-            39 => Ok(Self::KSTR),
-            40 => Ok(Self::KCDATA),
-            41 => Ok(Self::KSHORT),
-            42 => Ok(Self::KNUM),
-            43 => Ok(Self::KPRI),
-            44 => Ok(Self::KNIL),
-            45 => Ok(Self::UGET),
-            46 => Ok(Self::USETV),
-            47 => Ok(Self::USETS),
-            48 => Ok(Self::USETN),
-            49 => Ok(Self::USETP),
-            50 => Ok(Self::UCLO),
-            51 => Ok(Self::FNEW),
-            52 => Ok(Self::TNEW),
-            53 => Ok(Self::TDUP),
-            54 => Ok(Self::GGET),
-            55 => Ok(Self::GSET),
-            56 => Ok(Self::TGETV),
-            57 => Ok(Self::TGETS),
-            58 => Ok(Self::TGETB),
-            59 => Ok(Self::TGETR),
-            60 => Ok(Self::TSETV),
-            61 => Ok(Self::TSETS),
-            62 => Ok(Self::TSETB),
-            63 => Ok(Self::TSETM),
-            64 => Ok(Self::TSETR),
-            65 => Ok(Self::CALLM),
-            66 => Ok(Self::CALL),
-            67 => Ok(Self::CALLMT),
-            68 => Ok(Self::CALLT),
-            69 => Ok(Self::ITERC),
-            70 => Ok(Self::ITERN),
-            71 => Ok(Self::VARG),
-            72 => Ok(Self::ISNEXT),
-            73 => Ok(Self::RETM),
-            74 => Ok(Self::RET),
-            75 => Ok(Self::RET0),
-            76 => Ok(Self::RET1),
-            77 => Ok(Self::FORI),
-            78 => Ok(Self::JFORI),
-            79 => Ok(Self::FORL),
-            80 => Ok(Self::IFORL),
-            81 => Ok(Self::JFORL),
-            82 => Ok(Self::ITERL),
-            83 => Ok(Self::IITERL),
-            84 => Ok(Self::JITERL),
-            85 => Ok(Self::LOOP),
-            86 => Ok(Self::ILOOP),
-            87 => Ok(Self::JLOOP),
-            88 => Ok(Self::JMP),
-            89 => Ok(Self::FUNCF),
-            90 => Ok(Self::IFUNCF),
-            91 => Ok(Self::JFUNCF),
-            92 => Ok(Self::FUNCV),
-            93 => Ok(Self::IFUNCV),
-            94 => Ok(Self::JFUNCV),
-            95 => Ok(Self::FUNCC),
-            96 => Ok(Self::FUNCCW),
-            _ => Err(Error::LuaJitInvalidOpcodeNumber(value)),
-        }
-    }
+/// The opcodes of every LuaJIT version under one type, so analyses can be written once
+///
+/// Variants are named after LuaJIT's mnemonics, the ones only found in 2.1 included. The I- and
+/// J-prefixed ones are variants of a base opcode, see [`Opcode::base`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Opcode {
+    ISLT,
+    ISGE,
+    ISLE,
+    ISGT,
+    ISEQV,
+    ISNEV,
+    ISEQS,
+    ISNES,
+    ISEQN,
+    ISNEN,
+    ISEQP,
+    ISNEP,
+    ISTC,
+    ISFC,
+    IST,
+    ISF,
+    ISTYPE,
+    ISNUM,
+    MOV,
+    NOT,
+    UNM,
+    LEN,
+    ADDVN,
+    SUBVN,
+    MULVN,
+    DIVVN,
+    MODVN,
+    ADDNV,
+    SUBNV,
+    MULNV,
+    DIVNV,
+    MODNV,
+    ADDVV,
+    SUBVV,
+    MULVV,
+    DIVVV,
+    MODVV,
+    POW,
+    CAT,
+    KSTR,
+    KCDATA,
+    KSHORT,
+    KNUM,
+    KPRI,
+    KNIL,
+    UGET,
+    USETV,
+    USETS,
+    USETN,
+    USETP,
+    UCLO,
+    FNEW,
+    TNEW,
+    TDUP,
+    GGET,
+    GSET,
+    TGETV,
+    TGETS,
+    TGETB,
+    TGETR,
+    TSETV,
+    TSETS,
+    TSETB,
+    TSETM,
+    TSETR,
+    CALLM,
+    CALL,
+    CALLMT,
+    CALLT,
+    ITERC,
+    ITERN,
+    VARG,
+    ISNEXT,
+    RETM,
+    RET,
+    RET0,
+    RET1,
+    FORI,
+    JFORI,
+    FORL,
+    IFORL,
+    JFORL,
+    ITERL,
+    IITERL,
+    JITERL,
+    LOOP,
+    ILOOP,
+    JLOOP,
+    JMP,
+    FUNCF,
+    IFUNCF,
+    JFUNCF,
+    FUNCV,
+    IFUNCV,
+    JFUNCV,
+    FUNCC,
+    FUNCCW,
+    /// See [`LuaJitOpcode::Unknown`]
+    UNKNOWN,
 }
 
-impl TryFrom<u32> for LuaJit20Opcode {
-    type Error = Error;
-
-    fn try_from(value: u32) -> Result<Self> {
-        match value {
-            1 => Ok(Self::ISLT),
-            2 => Ok(Self::ISGE),
-            3 => Ok(Self::ISLE),
-            4 => Ok(Self::ISGT),
-            5 => Ok(Self::ISEQV),
-            6 => Ok(Self::ISNEV),
-            7 => Ok(Self::ISEQS),
-            8 => Ok(Self::ISNES),
-            9 => Ok(Self::ISEQN),
-            10 => Ok(Self::ISNEN),
-            11 => Ok(Self::ISEQP),
-            12 => Ok(Self::ISNEP),
-            13 => Ok(Self::ISTC),
-            14 => Ok(Self::ISFC),
-            15 => Ok(Self::IST),
-            16 => Ok(Self::ISF),
-            17 => Ok(Self::MOV),
-            18 => Ok(Self::NOT),
-            19 => Ok(Self::UNM),
-            20 => Ok(Self::LEN),
-            21 => Ok(Self::ADDVN),
-            22 => Ok(Self::SUBVN),
-            23 => Ok(Self::MULVN),
-            24 => Ok(Self::DIVVN),
-            25 => Ok(Self::MODVN),
-            26 => Ok(Self::ADDNV),
-            27 => Ok(Self::SUBNV),
-            28 => Ok(Self::MULNV),
-            29 => Ok(Self::DIVNV),
-            30 => Ok(Self::MODNV),
-            31 => Ok(Self::ADDVV),
-            33 => Ok(Self::SUBVV),
-            34 => Ok(Self::MULVV),
-            35 => Ok(Self::DIVVV),
-            36 => Ok(Self::MODVV),
-            37 => Ok(Self::POW),
-            38 => Ok(Self::CAT),
-            39 => Ok(Self::KSTR),
-            40 => Ok(Self::KCDATA),
-            41 => Ok(Self::KSHORT),
-            42 => Ok(Self::KNUM),
-            43 => Ok(Self::KPRI),
-            44 => Ok(Self::KNIL),
-            45 => Ok(Self::UGET),
-            46 => Ok(Self::USETV),
-            47 => Ok(Self::USETS),
-            48 => Ok(Self::USETN),
-            49 => Ok(Self::USETP),
-            50 => Ok(Self::UCLO),
-            51 => Ok(Self::FNEW),
-            52 => Ok(Self::TNEW),
-            53 => Ok(Self::TDUP),
-            54 => Ok(Self::GGET),
-            55 => Ok(Self::GSET),
-            56 => Ok(Self::TGETV),
-            57 => Ok(Self::TGETS),
-            58 => Ok(Self::TGETB),
-            59 => Ok(Self::TSETV),
-            60 => Ok(Self::TSETS),
-            61 => Ok(Self::TSETB),
-            62 => Ok(Self::TSETM),
-            63 => Ok(Self::CALLM),
-            64 => Ok(Self::CALL),
-            65 => Ok(Self::CALLMT),
-            66 => Ok(Self::CALLT),
-            67 => Ok(Self::ITERC),
-            68 => Ok(Self::ITERN),
-            69 => Ok(Self::VARG),
-            70 => Ok(Self::ISNEXT),
-            71 => Ok(Self::RETM),
-            72 => Ok(Self::RET),
-            73 => Ok(Self::RET0),
-            74 => Ok(Self::RET1),
-            75 => Ok(Self::FORI),
-            76 => Ok(Self::JFORI),
-            77 => Ok(Self::FORL),
-            78 => Ok(Self::IFORL),
-            79 => Ok(Self::JFORL),
-            80 => Ok(Self::ITERL),
-            81 => Ok(Self::IITERL),
-            82 => Ok(Self::JITERL),
-            83 => Ok(Self::LOOP),
-            84 => Ok(Self::ILOOP),
-            85 => Ok(Self::JLOOP),
-            86 => Ok(Self::JMP),
-            87 => Ok(Self::FUNCF),
-            88 => Ok(Self::IFUNCF),
-            89 => Ok(Self::JFUNCF),
-            90 => Ok(Self::FUNCV),
-            91 => Ok(Self::IFUNCV),
-            92 => Ok(Self::JFUNCV),
-            93 => Ok(Self::FUNCC),
-            94 => Ok(Self::FUNCCW),
-            _ => Err(Error::LuaJitInvalidOpcodeNumber(value)),
-        }
-    }
+/// How an instruction uses the stack slot an operand refers to
+///
+/// Base operands (`T_BS`, `T_RBS`) stand for the first slot of a range, e.g. the arguments and
+/// results of a call.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SlotAccess {
+    Read,
+    Written,
+    ReadWritten,
 }
 
-impl From<LuaJitOpcode> for [Option<ArgumentType>; 3] {
-    fn from(value: LuaJitOpcode) -> Self {
-        (&value).into()
-    }
+/// Which flavour of its base opcode an opcode is, see [`Opcode::base`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum OpcodeVariant {
+    /// As emitted by the parser, loops and functions count towards hot spots
+    Base,
+    /// `I`-prefixed, run by the interpreter without hot counting
+    Interpreted,
+    /// `J`-prefixed, patched in by the JIT compiler to run a trace
+    Compiled,
 }
 
-impl From<&LuaJitOpcode> for [Option<ArgumentType>; 3] {
-    fn from(value: &LuaJitOpcode) -> Self {
-        match value {
-            LuaJitOpcode::Lj20(lua_jit20_opcode) => lua_jit20_opcode.into(),
-            LuaJitOpcode::Lj21(lua_jit21_opcode) => lua_jit21_opcode.into(),
+impl Opcode {
+    /// Whether the operands are laid out as `A`, `B` and `C` or as `A` and `D`
+    pub fn format(self) -> InstructionOperandsFormat {
+        use Opcode::*;
+        match self {
+            ADDVN | SUBVN | MULVN | DIVVN | MODVN | ADDNV | SUBNV | MULNV | DIVNV | MODNV
+            | ADDVV | SUBVV | MULVV | DIVVV | MODVV | POW | CAT | TGETV | TGETS | TGETB | TGETR
+            | TSETV | TSETS | TSETB | TSETR | CALLM | CALL | ITERC | ITERN | VARG => {
+                InstructionOperandsFormat::Abc
+            }
+            _ => InstructionOperandsFormat::Ad,
         }
     }
-}
-
-impl From<LuaJit20Opcode> for [Option<ArgumentType>; 3] {
-    fn from(value: LuaJit20Opcode) -> Self {
-        (&value).into()
-    }
-}
 
-impl From<&LuaJit20Opcode> for [Option<ArgumentType>; 3] {
-    fn from(val: &LuaJit20Opcode) -> Self {
+    /// Types of the `A`, `B` and `C`/`D` operands, `None` for unused ones
+    pub fn argument_types(self) -> [Option<ArgumentType>; 3] {
         use ArgumentType::*;
-        use LuaJit20Opcode::*;
-        match val {
+        use Opcode::*;
+        match self {
             ISLT | ISGE | ISLE | ISGT | ISEQV | ISNEV => [Some(T_VAR), None, Some(T_VAR)],
 
-            ISEQS | ISNES => [Some(T_VAR), None, Some(T_VAR)],
+            ISEQS | ISNES => [Some(T_VAR), None, Some(T_STR)],
 
             ISEQN | ISNEN => [Some(T_VAR), None, Some(T_NUM)],
 
@@ -491,7 +410,7 @@ impl From<&LuaJit20Opcode> for [Option<ArgumentType>; 3] {
 
             IST | ISF => [None, None, Some(T_VAR)],
 
-            // ISTYPE | ISNUM => [Some(T_VAR), None, Some(T_LIT)],
+            ISTYPE | ISNUM => [Some(T_VAR), None, Some(T_LIT)],
             MOV | NOT | UNM | LEN => [Some(T_DST), None, Some(T_VAR)],
 
             CAT => [Some(T_DST), Some(T_RBS), Some(T_RBS)],
@@ -526,14 +445,14 @@ impl From<&LuaJit20Opcode> for [Option<ArgumentType>; 3] {
             TGETV => [Some(T_DST), Some(T_VAR), Some(T_VAR)],
             TGETS => [Some(T_DST), Some(T_VAR), Some(T_STR)],
             TGETB => [Some(T_DST), Some(T_VAR), Some(T_LIT)],
-            //TGETR => [Some(T_DST), Some(T_VAR), Some(T_VAR)],
+            TGETR => [Some(T_DST), Some(T_VAR), Some(T_VAR)],
             TSETV => [Some(T_VAR), Some(T_VAR), Some(T_VAR)],
             TSETS => [Some(T_VAR), Some(T_VAR), Some(T_STR)],
             TSETB => [Some(T_VAR), Some(T_VAR), Some(T_LIT)],
 
             TSETM => [Some(T_BS), None, Some(T_NUM)],
 
-            //TSETR => [Some(T_VAR), Some(T_VAR), Some(T_VAR)],
+            TSETR => [Some(T_VAR), Some(T_VAR), Some(T_VAR)],
             CALL | CALLM => [Some(T_BS), Some(T_LIT), Some(T_LIT)],
 
             CALLMT | CALLT => [Some(T_BS), None, Some(T_LIT)],
@@ -545,11 +464,12 @@ impl From<&LuaJit20Opcode> for [Option<ArgumentType>; 3] {
             RETM => [Some(T_BS), None, Some(T_LIT)],
             RET | RET0 | RET1 => [Some(T_RBS), None, Some(T_LIT)],
 
-            FORI | JFORI | FORL | IFORL | JFORL | ITERL | IITERL => [Some(T_BS), None, Some(T_JMP)],
+            FORI | JFORI | FORL | IFORL | ITERL | IITERL => [Some(T_BS), None, Some(T_JMP)],
 
-            JITERL => [Some(T_BS), None, Some(T_LIT)],
+            // The compiled variants hold the trace number instead of the jump
+            JFORL | JITERL => [Some(T_BS), None, Some(T_LIT)],
 
-            LOOP | ILOOP => [Some(T_RBS), None, Some(T_LIT)],
+            LOOP | ILOOP => [Some(T_RBS), None, Some(T_JMP)],
             JLOOP => [Some(T_RBS), None, Some(T_LIT)],
 
             JMP => [Some(T_RBS), None, Some(T_JMP)],
@@ -558,195 +478,190 @@ impl From<&LuaJit20Opcode> for [Option<ArgumentType>; 3] {
 
             JFUNCF | JFUNCV => [Some(T_RBS), None, Some(T_LIT)],
 
-            ADDVN | SUBVN | MULVN | DIVVN | MODVN | ADDNV | SUBNV | MULNV | DIVNV | MODNV
-            | ADDVV | SUBVV | MULVV | DIVVV | MODVV | POW => {
+            UNKNOWN => [None, None, None],
+
+            ADDVN | SUBVN | MULVN | DIVVN | MODVN | ADDNV | SUBNV | MULNV | DIVNV | MODNV => {
                 [Some(T_DST), Some(T_VAR), Some(T_NUM)]
             }
-        }
-    }
-}
 
-impl From<LuaJit21Opcode> for [Option<ArgumentType>; 3] {
-    fn from(val: LuaJit21Opcode) -> Self {
-        (&val).into()
+            ADDVV | SUBVV | MULVV | DIVVV | MODVV | POW => [Some(T_DST), Some(T_VAR), Some(T_VAR)],
+        }
     }
-}
-
-impl From<&LuaJit21Opcode> for [Option<ArgumentType>; 3] {
-    fn from(val: &LuaJit21Opcode) -> Self {
-        use ArgumentType::*;
-        use LuaJit21Opcode::*;
-        match val {
-            ISLT | ISGE | ISLE | ISGT | ISEQV | ISNEV => [Some(T_VAR), None, Some(T_VAR)],
-
-            ISEQS | ISNES => [Some(T_VAR), None, Some(T_VAR)],
-
-            ISEQN | ISNEN => [Some(T_VAR), None, Some(T_NUM)],
-
-            ISEQP | ISNEP => [Some(T_VAR), None, Some(T_PRI)],
-
-            ISTC | ISFC => [Some(T_DST), None, Some(T_VAR)],
-
-            IST | ISF => [None, None, Some(T_VAR)],
-
-            ISTYPE | ISNUM => [Some(T_VAR), None, Some(T_LIT)],
-            MOV | NOT | UNM | LEN => [Some(T_DST), None, Some(T_VAR)],
-
-            CAT => [Some(T_DST), Some(T_RBS), Some(T_RBS)],
-
-            KSTR => [Some(T_DST), None, Some(T_STR)],
-            KCDATA => [Some(T_DST), None, Some(T_CDT)],
-            KSHORT => [Some(T_DST), None, Some(T_SLIT)],
-            KNUM => [Some(T_DST), None, Some(T_NUM)],
-            KPRI => [Some(T_DST), None, Some(T_PRI)],
-
-            KNIL => [Some(T_BS), None, Some(T_BS)],
-
-            UGET => [Some(T_DST), None, Some(T_UV)],
 
-            USETV => [Some(T_UV), None, Some(T_VAR)],
-            USETS => [Some(T_UV), None, Some(T_STR)],
-            USETN => [Some(T_UV), None, Some(T_NUM)],
-            USETP => [Some(T_UV), None, Some(T_PRI)],
-
-            UCLO => [Some(T_RBS), None, Some(T_JMP)],
-
-            FNEW => [Some(T_DST), None, Some(T_FUN)],
+    /// How the `A`, `B` and `C`/`D` operands access the stack, `None` for those that are no slot
+    /// or whose slot is left alone
+    pub fn slot_access(self) -> [Option<SlotAccess>; 3] {
+        use Opcode::*;
+        use SlotAccess::*;
+        match self.base() {
+            ISLT | ISGE | ISLE | ISGT | ISEQV | ISNEV => [Some(Read), None, Some(Read)],
+            ISEQS | ISNES | ISEQN | ISNEN | ISEQP | ISNEP | ISTYPE | ISNUM => {
+                [Some(Read), None, None]
+            }
+            // The tested value is only copied to `A` when the test passes
+            ISTC | ISFC | MOV | NOT | UNM | LEN => [Some(Written), None, Some(Read)],
+            IST | ISF | USETV => [None, None, Some(Read)],
 
-            TNEW => [Some(T_DST), None, Some(T_LIT)],
+            ADDVN | SUBVN | MULVN | DIVVN | MODVN | ADDNV | SUBNV | MULNV | DIVNV | MODNV
+            | TGETS | TGETB => [Some(Written), Some(Read), None],
+            ADDVV | SUBVV | MULVV | DIVVV | MODVV | POW | CAT | TGETV | TGETR => {
+                [Some(Written), Some(Read), Some(Read)]
+            }
 
-            TDUP => [Some(T_DST), None, Some(T_TAB)],
+            KSTR | KCDATA | KSHORT | KNUM | KPRI | UGET | FNEW | TNEW | TDUP | GGET | VARG => {
+                [Some(Written), None, None]
+            }
+            KNIL => [Some(Written), None, Some(Written)],
 
-            GGET => [Some(T_DST), None, Some(T_STR)],
+            TSETV | TSETR => [Some(Read), Some(Read), Some(Read)],
+            TSETS | TSETB => [Some(Read), Some(Read), None],
+            GSET | CALLT | CALLMT | RETM | RET | RET1 => [Some(Read), None, None],
+            // The table, or the iterator state for `ISNEXT`, sits right below `A`
+            TSETM | ISNEXT => [Some(Read), None, None],
 
-            GSET => [Some(T_VAR), None, Some(T_STR)],
+            // Calls overwrite the function and arguments with the results
+            CALL | CALLM | ITERC | ITERN => [Some(ReadWritten), None, None],
+            // Loops update their control variables, `ITERL` copies the next key below `A`
+            FORI | FORL | ITERL => [Some(ReadWritten), None, None],
 
-            TGETV => [Some(T_DST), Some(T_VAR), Some(T_VAR)],
-            TGETS => [Some(T_DST), Some(T_VAR), Some(T_STR)],
-            TGETB => [Some(T_DST), Some(T_VAR), Some(T_LIT)],
-            TGETR => [Some(T_DST), Some(T_VAR), Some(T_VAR)],
-            TSETV => [Some(T_VAR), Some(T_VAR), Some(T_VAR)],
-            TSETS => [Some(T_VAR), Some(T_VAR), Some(T_STR)],
-            TSETB => [Some(T_VAR), Some(T_VAR), Some(T_LIT)],
-
-            TSETM => [Some(T_BS), None, Some(T_NUM)],
+            _ => [None, None, None],
+        }
+    }
 
-            TSETR => [Some(T_VAR), Some(T_VAR), Some(T_VAR)],
-            CALL | CALLM => [Some(T_BS), Some(T_LIT), Some(T_LIT)],
+    /// May continue somewhere else than at the next instruction
+    ///
+    /// Comparisons and tests are branches, as they decide whether the `JMP` following them is
+    /// taken. `LOOP` is not, its target is only used by the JIT compiler.
+    pub fn is_branch(self) -> bool {
+        use Opcode::*;
+        self.is_comparison() || matches!(self.base(), UCLO | ISNEXT | FORI | FORL | ITERL | JMP)
+    }
 
-            CALLMT | CALLT => [Some(T_BS), None, Some(T_LIT)],
+    /// Comparison or test, which is always followed by the `JMP` it takes when it holds and skips
+    /// otherwise
+    pub fn is_comparison(self) -> bool {
+        use Opcode::*;
+        matches!(
+            self,
+            ISLT | ISGE
+                | ISLE
+                | ISGT
+                | ISEQV
+                | ISNEV
+                | ISEQS
+                | ISNES
+                | ISEQN
+                | ISNEN
+                | ISEQP
+                | ISNEP
+                | ISTC
+                | ISFC
+                | IST
+                | ISF
+        )
+    }
 
-            ITERC | ITERN | VARG => [Some(T_BS), Some(T_LIT), Some(T_LIT)],
+    /// Calls a function, iterator calls and tail calls included
+    pub fn is_call(self) -> bool {
+        use Opcode::*;
+        matches!(self, CALLM | CALL | CALLMT | CALLT | ITERC | ITERN)
+    }
 
-            ISNEXT => [Some(T_BS), None, Some(T_JMP)],
+    /// Leaves the function, tail calls included
+    pub fn is_return(self) -> bool {
+        use Opcode::*;
+        matches!(self, RETM | RET | RET0 | RET1 | CALLMT | CALLT)
+    }
 
-            RETM => [Some(T_BS), None, Some(T_LIT)],
-            RET | RET0 | RET1 => [Some(T_RBS), None, Some(T_LIT)],
+    /// Closes the body of a numeric, iterator or `while`/`repeat` loop
+    pub fn is_loop(self) -> bool {
+        use Opcode::*;
+        matches!(self.base(), FORL | ITERL | LOOP)
+    }
 
-            FORI | JFORI | FORL | IFORL | JFORL | ITERL | IITERL => [Some(T_BS), None, Some(T_JMP)],
+    /// The opcode the parser emits, which the `I` and `J` variants stand in for
+    ///
+    /// `IFORL` and `JFORL` both are `FORL`, for instance. Any other opcode is its own base.
+    pub fn base(self) -> Opcode {
+        use Opcode::*;
+        match self {
+            JFORI => FORI,
+            IFORL | JFORL => FORL,
+            IITERL | JITERL => ITERL,
+            ILOOP | JLOOP => LOOP,
+            IFUNCF | JFUNCF => FUNCF,
+            IFUNCV | JFUNCV => FUNCV,
+            opcode => opcode,
+        }
+    }
 
-            JITERL => [Some(T_BS), None, Some(T_LIT)],
+    pub fn variant(self) -> OpcodeVariant {
+        use Opcode::*;
+        match self {
+            IFORL | IITERL | ILOOP | IFUNCF | IFUNCV => OpcodeVariant::Interpreted,
+            JFORI | JFORL | JITERL | JLOOP | JFUNCF | JFUNCV => OpcodeVariant::Compiled,
+            _ => OpcodeVariant::Base,
+        }
+    }
+}
 
-            LOOP | ILOOP => [Some(T_RBS), None, Some(T_LIT)],
-            JLOOP => [Some(T_RBS), None, Some(T_LIT)],
+impl LuaJitOpcode {
+    /// The opcode regardless of the version it was decoded from
+    pub fn canonical(&self) -> Opcode {
+        match self {
+            LuaJitOpcode::Lj20(lua_jit20_opcode) => lua_jit20_opcode.into(),
+            LuaJitOpcode::Lj21(lua_jit21_opcode) => lua_jit21_opcode.into(),
+            LuaJitOpcode::Unknown(_) => Opcode::UNKNOWN,
+        }
+    }
+}
 
-            JMP => [Some(T_RBS), None, Some(T_JMP)],
+impl fmt::Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Variant names are LuaJIT's own mnemonics
+        f.pad(&format!("{self:?}"))
+    }
+}
 
-            FUNCF | IFUNCF | FUNCV | IFUNCV | FUNCC | FUNCCW => [Some(T_RBS), None, None],
+impl fmt::Display for LuaJitOpcode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.canonical(), f)
+    }
+}
 
-            JFUNCF | JFUNCV => [Some(T_RBS), None, Some(T_LIT)],
+impl From<LuaJitOpcode> for [Option<ArgumentType>; 3] {
+    fn from(value: LuaJitOpcode) -> Self {
+        (&value).into()
+    }
+}
 
-            ADDVN | SUBVN | MULVN | DIVVN | MODVN | ADDNV | SUBNV | MULNV | DIVNV | MODNV
-            | ADDVV | SUBVV | MULVV | DIVVV | MODVV | POW => {
-                [Some(T_DST), Some(T_VAR), Some(T_NUM)]
-            }
-        }
+impl From<&LuaJitOpcode> for [Option<ArgumentType>; 3] {
+    fn from(value: &LuaJitOpcode) -> Self {
+        value.canonical().argument_types()
     }
 }
 
-impl From<LuaJit20Opcode> for InstructionOperandsFormat {
+impl From<LuaJit20Opcode> for [Option<ArgumentType>; 3] {
     fn from(value: LuaJit20Opcode) -> Self {
         (&value).into()
     }
 }
 
-impl From<&LuaJit20Opcode> for InstructionOperandsFormat {
+impl From<&LuaJit20Opcode> for [Option<ArgumentType>; 3] {
     fn from(value: &LuaJit20Opcode) -> Self {
-        match value {
-            LuaJit20Opcode::ADDVN
-            | LuaJit20Opcode::SUBVN
-            | LuaJit20Opcode::MULVN
-            | LuaJit20Opcode::DIVVN
-            | LuaJit20Opcode::MODVN
-            | LuaJit20Opcode::ADDNV
-            | LuaJit20Opcode::SUBNV
-            | LuaJit20Opcode::MULNV
-            | LuaJit20Opcode::DIVNV
-            | LuaJit20Opcode::MODNV
-            | LuaJit20Opcode::ADDVV
-            | LuaJit20Opcode::SUBVV
-            | LuaJit20Opcode::MULVV
-            | LuaJit20Opcode::DIVVV
-            | LuaJit20Opcode::MODVV
-            | LuaJit20Opcode::POW
-            | LuaJit20Opcode::CAT
-            | LuaJit20Opcode::TGETV
-            | LuaJit20Opcode::TGETS
-            | LuaJit20Opcode::TGETB
-            | LuaJit20Opcode::TSETV
-            | LuaJit20Opcode::TSETS
-            | LuaJit20Opcode::TSETB
-            | LuaJit20Opcode::CALLM
-            | LuaJit20Opcode::CALL
-            | LuaJit20Opcode::ITERC
-            | LuaJit20Opcode::ITERN
-            | LuaJit20Opcode::VARG => Self::Abc,
-            _ => Self::Ad,
-        }
+        Opcode::from(value).argument_types()
     }
 }
 
-impl From<LuaJit21Opcode> for InstructionOperandsFormat {
+impl From<LuaJit21Opcode> for [Option<ArgumentType>; 3] {
     fn from(value: LuaJit21Opcode) -> Self {
         (&value).into()
     }
 }
 
-impl From<&LuaJit21Opcode> for InstructionOperandsFormat {
+impl From<&LuaJit21Opcode> for [Option<ArgumentType>; 3] {
     fn from(value: &LuaJit21Opcode) -> Self {
-        match value {
-            LuaJit21Opcode::ADDVN
-            | LuaJit21Opcode::SUBVN
-            | LuaJit21Opcode::MULVN
-            | LuaJit21Opcode::DIVVN
-            | LuaJit21Opcode::MODVN
-            | LuaJit21Opcode::ADDNV
-            | LuaJit21Opcode::SUBNV
-            | LuaJit21Opcode::MULNV
-            | LuaJit21Opcode::DIVNV
-            | LuaJit21Opcode::MODNV
-            | LuaJit21Opcode::ADDVV
-            | LuaJit21Opcode::SUBVV
-            | LuaJit21Opcode::MULVV
-            | LuaJit21Opcode::DIVVV
-            | LuaJit21Opcode::MODVV
-            | LuaJit21Opcode::POW
-            | LuaJit21Opcode::CAT
-            | LuaJit21Opcode::TGETV
-            | LuaJit21Opcode::TGETS
-            | LuaJit21Opcode::TGETB
-            | LuaJit21Opcode::TGETR
-            | LuaJit21Opcode::TSETV
-            | LuaJit21Opcode::TSETS
-            | LuaJit21Opcode::TSETB
-            | LuaJit21Opcode::TSETR
-            | LuaJit21Opcode::CALLM
-            | LuaJit21Opcode::CALL
-            | LuaJit21Opcode::ITERC
-            | LuaJit21Opcode::ITERN
-            | LuaJit21Opcode::VARG => Self::Abc,
-            _ => Self::Ad,
-        }
+        Opcode::from(value).argument_types()
     }
 }
 
@@ -758,9 +673,222 @@ impl From<LuaJitOpcode> for InstructionOperandsFormat {
 
 impl From<&LuaJitOpcode> for InstructionOperandsFormat {
     fn from(value: &LuaJitOpcode) -> Self {
+        value.canonical().format()
+    }
+}
+
+impl From<LuaJit20Opcode> for Opcode {
+    fn from(value: LuaJit20Opcode) -> Self {
+        (&value).into()
+    }
+}
+
+impl From<&LuaJit20Opcode> for Opcode {
+    fn from(value: &LuaJit20Opcode) -> Self {
         match value {
-            LuaJitOpcode::Lj20(lua_jit20_opcode) => lua_jit20_opcode.into(),
-            LuaJitOpcode::Lj21(lua_jit21_opcode) => lua_jit21_opcode.into(),
+            LuaJit20Opcode::ISLT => Self::ISLT,
+            LuaJit20Opcode::ISGE => Self::ISGE,
+            LuaJit20Opcode::ISLE => Self::ISLE,
+            LuaJit20Opcode::ISGT => Self::ISGT,
+            LuaJit20Opcode::ISEQV => Self::ISEQV,
+            LuaJit20Opcode::ISNEV => Self::ISNEV,
+            LuaJit20Opcode::ISEQS => Self::ISEQS,
+            LuaJit20Opcode::ISNES => Self::ISNES,
+            LuaJit20Opcode::ISEQN => Self::ISEQN,
+            LuaJit20Opcode::ISNEN => Self::ISNEN,
+            LuaJit20Opcode::ISEQP => Self::ISEQP,
+            LuaJit20Opcode::ISNEP => Self::ISNEP,
+            LuaJit20Opcode::ISTC => Self::ISTC,
+            LuaJit20Opcode::ISFC => Self::ISFC,
+            LuaJit20Opcode::IST => Self::IST,
+            LuaJit20Opcode::ISF => Self::ISF,
+            LuaJit20Opcode::MOV => Self::MOV,
+            LuaJit20Opcode::NOT => Self::NOT,
+            LuaJit20Opcode::UNM => Self::UNM,
+            LuaJit20Opcode::LEN => Self::LEN,
+            LuaJit20Opcode::ADDVN => Self::ADDVN,
+            LuaJit20Opcode::SUBVN => Self::SUBVN,
+            LuaJit20Opcode::MULVN => Self::MULVN,
+            LuaJit20Opcode::DIVVN => Self::DIVVN,
+            LuaJit20Opcode::MODVN => Self::MODVN,
+            LuaJit20Opcode::ADDNV => Self::ADDNV,
+            LuaJit20Opcode::SUBNV => Self::SUBNV,
+            LuaJit20Opcode::MULNV => Self::MULNV,
+            LuaJit20Opcode::DIVNV => Self::DIVNV,
+            LuaJit20Opcode::MODNV => Self::MODNV,
+            LuaJit20Opcode::ADDVV => Self::ADDVV,
+            LuaJit20Opcode::SUBVV => Self::SUBVV,
+            LuaJit20Opcode::MULVV => Self::MULVV,
+            LuaJit20Opcode::DIVVV => Self::DIVVV,
+            LuaJit20Opcode::MODVV => Self::MODVV,
+            LuaJit20Opcode::POW => Self::POW,
+            LuaJit20Opcode::CAT => Self::CAT,
+            LuaJit20Opcode::KSTR => Self::KSTR,
+            LuaJit20Opcode::KCDATA => Self::KCDATA,
+            LuaJit20Opcode::KSHORT => Self::KSHORT,
+            LuaJit20Opcode::KNUM => Self::KNUM,
+            LuaJit20Opcode::KPRI => Self::KPRI,
+            LuaJit20Opcode::KNIL => Self::KNIL,
+            LuaJit20Opcode::UGET => Self::UGET,
+            LuaJit20Opcode::USETV => Self::USETV,
+            LuaJit20Opcode::USETS => Self::USETS,
+            LuaJit20Opcode::USETN => Self::USETN,
+            LuaJit20Opcode::USETP => Self::USETP,
+            LuaJit20Opcode::UCLO => Self::UCLO,
+            LuaJit20Opcode::FNEW => Self::FNEW,
+            LuaJit20Opcode::TNEW => Self::TNEW,
+            LuaJit20Opcode::TDUP => Self::TDUP,
+            LuaJit20Opcode::GGET => Self::GGET,
+            LuaJit20Opcode::GSET => Self::GSET,
+            LuaJit20Opcode::TGETV => Self::TGETV,
+            LuaJit20Opcode::TGETS => Self::TGETS,
+            LuaJit20Opcode::TGETB => Self::TGETB,
+            LuaJit20Opcode::TSETV => Self::TSETV,
+            LuaJit20Opcode::TSETS => Self::TSETS,
+            LuaJit20Opcode::TSETB => Self::TSETB,
+            LuaJit20Opcode::TSETM => Self::TSETM,
+            LuaJit20Opcode::CALLM => Self::CALLM,
+            LuaJit20Opcode::CALL => Self::CALL,
+            LuaJit20Opcode::CALLMT => Self::CALLMT,
+            LuaJit20Opcode::CALLT => Self::CALLT,
+            LuaJit20Opcode::ITERC => Self::ITERC,
+            LuaJit20Opcode::ITERN => Self::ITERN,
+            LuaJit20Opcode::VARG => Self::VARG,
+            LuaJit20Opcode::ISNEXT => Self::ISNEXT,
+            LuaJit20Opcode::RETM => Self::RETM,
+            LuaJit20Opcode::RET => Self::RET,
+            LuaJit20Opcode::RET0 => Self::RET0,
+            LuaJit20Opcode::RET1 => Self::RET1,
+            LuaJit20Opcode::FORI => Self::FORI,
+            LuaJit20Opcode::JFORI => Self::JFORI,
+            LuaJit20Opcode::FORL => Self::FORL,
+            LuaJit20Opcode::IFORL => Self::IFORL,
+            LuaJit20Opcode::JFORL => Self::JFORL,
+            LuaJit20Opcode::ITERL => Self::ITERL,
+            LuaJit20Opcode::IITERL => Self::IITERL,
+            LuaJit20Opcode::JITERL => Self::JITERL,
+            LuaJit20Opcode::LOOP => Self::LOOP,
+            LuaJit20Opcode::ILOOP => Self::ILOOP,
+            LuaJit20Opcode::JLOOP => Self::JLOOP,
+            LuaJit20Opcode::JMP => Self::JMP,
+            LuaJit20Opcode::FUNCF => Self::FUNCF,
+            LuaJit20Opcode::IFUNCF => Self::IFUNCF,
+            LuaJit20Opcode::JFUNCF => Self::JFUNCF,
+            LuaJit20Opcode::FUNCV => Self::FUNCV,
+            LuaJit20Opcode::IFUNCV => Self::IFUNCV,
+            LuaJit20Opcode::JFUNCV => Self::JFUNCV,
+            LuaJit20Opcode::FUNCC => Self::FUNCC,
+            LuaJit20Opcode::FUNCCW => Self::FUNCCW,
+        }
+    }
+}
+
+impl From<LuaJit21Opcode> for Opcode {
+    fn from(value: LuaJit21Opcode) -> Self {
+        (&value).into()
+    }
+}
+
+impl From<&LuaJit21Opcode> for Opcode {
+    fn from(value: &LuaJit21Opcode) -> Self {
+        match value {
+            LuaJit21Opcode::ISLT => Self::ISLT,
+            LuaJit21Opcode::ISGE => Self::ISGE,
+            LuaJit21Opcode::ISLE => Self::ISLE,
+            LuaJit21Opcode::ISGT => Self::ISGT,
+            LuaJit21Opcode::ISEQV => Self::ISEQV,
+            LuaJit21Opcode::ISNEV => Self::ISNEV,
+            LuaJit21Opcode::ISEQS => Self::ISEQS,
+            LuaJit21Opcode::ISNES => Self::ISNES,
+            LuaJit21Opcode::ISEQN => Self::ISEQN,
+            LuaJit21Opcode::ISNEN => Self::ISNEN,
+            LuaJit21Opcode::ISEQP => Self::ISEQP,
+            LuaJit21Opcode::ISNEP => Self::ISNEP,
+            LuaJit21Opcode::ISTC => Self::ISTC,
+            LuaJit21Opcode::ISFC => Self::ISFC,
+            LuaJit21Opcode::IST => Self::IST,
+            LuaJit21Opcode::ISF => Self::ISF,
+            LuaJit21Opcode::ISTYPE => Self::ISTYPE,
+            LuaJit21Opcode::ISNUM => Self::ISNUM,
+            LuaJit21Opcode::MOV => Self::MOV,
+            LuaJit21Opcode::NOT => Self::NOT,
+            LuaJit21Opcode::UNM => Self::UNM,
+            LuaJit21Opcode::LEN => Self::LEN,
+            LuaJit21Opcode::ADDVN => Self::ADDVN,
+            LuaJit21Opcode::SUBVN => Self::SUBVN,
+            LuaJit21Opcode::MULVN => Self::MULVN,
+            LuaJit21Opcode::DIVVN => Self::DIVVN,
+            LuaJit21Opcode::MODVN => Self::MODVN,
+            LuaJit21Opcode::ADDNV => Self::ADDNV,
+            LuaJit21Opcode::SUBNV => Self::SUBNV,
+            LuaJit21Opcode::MULNV => Self::MULNV,
+            LuaJit21Opcode::DIVNV => Self::DIVNV,
+            LuaJit21Opcode::MODNV => Self::MODNV,
+            LuaJit21Opcode::ADDVV => Self::ADDVV,
+            LuaJit21Opcode::SUBVV => Self::SUBVV,
+            LuaJit21Opcode::MULVV => Self::MULVV,
+            LuaJit21Opcode::DIVVV => Self::DIVVV,
+            LuaJit21Opcode::MODVV => Self::MODVV,
+            LuaJit21Opcode::POW => Self::POW,
+            LuaJit21Opcode::CAT => Self::CAT,
+            LuaJit21Opcode::KSTR => Self::KSTR,
+            LuaJit21Opcode::KCDATA => Self::KCDATA,
+            LuaJit21Opcode::KSHORT => Self::KSHORT,
+            LuaJit21Opcode::KNUM => Self::KNUM,
+            LuaJit21Opcode::KPRI => Self::KPRI,
+            LuaJit21Opcode::KNIL => Self::KNIL,
+            LuaJit21Opcode::UGET => Self::UGET,
+            LuaJit21Opcode::USETV => Self::USETV,
+            LuaJit21Opcode::USETS => Self::USETS,
+            LuaJit21Opcode::USETN => Self::USETN,
+            LuaJit21Opcode::USETP => Self::USETP,
+            LuaJit21Opcode::UCLO => Self::UCLO,
+            LuaJit21Opcode::FNEW => Self::FNEW,
+            LuaJit21Opcode::TNEW => Self::TNEW,
+            LuaJit21Opcode::TDUP => Self::TDUP,
+            LuaJit21Opcode::GGET => Self::GGET,
+            LuaJit21Opcode::GSET => Self::GSET,
+            LuaJit21Opcode::TGETV => Self::TGETV,
+            LuaJit21Opcode::TGETS => Self::TGETS,
+            LuaJit21Opcode::TGETB => Self::TGETB,
+            LuaJit21Opcode::TGETR => Self::TGETR,
+            LuaJit21Opcode::TSETV => Self::TSETV,
+            LuaJit21Opcode::TSETS => Self::TSETS,
+            LuaJit21Opcode::TSETB => Self::TSETB,
+            LuaJit21Opcode::TSETM => Self::TSETM,
+            LuaJit21Opcode::TSETR => Self::TSETR,
+            LuaJit21Opcode::CALLM => Self::CALLM,
+            LuaJit21Opcode::CALL => Self::CALL,
+            LuaJit21Opcode::CALLMT => Self::CALLMT,
+            LuaJit21Opcode::CALLT => Self::CALLT,
+            LuaJit21Opcode::ITERC => Self::ITERC,
+            LuaJit21Opcode::ITERN => Self::ITERN,
+            LuaJit21Opcode::VARG => Self::VARG,
+            LuaJit21Opcode::ISNEXT => Self::ISNEXT,
+            LuaJit21Opcode::RETM => Self::RETM,
+            LuaJit21Opcode::RET => Self::RET,
+            LuaJit21Opcode::RET0 => Self::RET0,
+            LuaJit21Opcode::RET1 => Self::RET1,
+            LuaJit21Opcode::FORI => Self::FORI,
+            LuaJit21Opcode::JFORI => Self::JFORI,
+            LuaJit21Opcode::FORL => Self::FORL,
+            LuaJit21Opcode::IFORL => Self::IFORL,
+            LuaJit21Opcode::JFORL => Self::JFORL,
+            LuaJit21Opcode::ITERL => Self::ITERL,
+            LuaJit21Opcode::IITERL => Self::IITERL,
+            LuaJit21Opcode::JITERL => Self::JITERL,
+            LuaJit21Opcode::LOOP => Self::LOOP,
+            LuaJit21Opcode::ILOOP => Self::ILOOP,
+            LuaJit21Opcode::JLOOP => Self::JLOOP,
+            LuaJit21Opcode::JMP => Self::JMP,
+            LuaJit21Opcode::FUNCF => Self::FUNCF,
+            LuaJit21Opcode::IFUNCF => Self::IFUNCF,
+            LuaJit21Opcode::JFUNCF => Self::JFUNCF,
+            LuaJit21Opcode::FUNCV => Self::FUNCV,
+            LuaJit21Opcode::IFUNCV => Self::IFUNCV,
+            LuaJit21Opcode::JFUNCV => Self::JFUNCV,
+            LuaJit21Opcode::FUNCC => Self::FUNCC,
+            LuaJit21Opcode::FUNCCW => Self::FUNCCW,
         }
     }
 }
@@ -0,0 +1,109 @@
+use super::{
+    header::LuaJitHeader,
+    revision::{LuaJitRevision, OpcodeTable},
+    DecodeLimit, Error, Result,
+};
+use crate::decoder::error::Diagnostic;
+
+/// Upper bounds and recovery behaviour used while decoding untrusted dumps
+///
+/// Every limit is `None` (unlimited) by default. Exceeding one makes decoding fail with
+/// [`Error::LuaJitLimitExceeded`]. Counts and length prefixes are checked as soon as they are read,
+/// before anything is allocated for them, and zero terminated names stop being read once they are
+/// too long.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct DecodeOptions {
+    /// Size of the whole dump in bytes
+    pub max_file_size: Option<u64>,
+    /// Number of prototypes in the dump
+    pub max_prototypes: Option<usize>,
+    /// Number of instructions in a single prototype
+    pub max_instructions: Option<u32>,
+    /// Number of GC and numeric constants in a single prototype, and of entries in a table constant
+    pub max_constants: Option<u32>,
+    /// Length in bytes of the chunk name, string constants, upvalue and variable names
+    pub max_string_length: Option<u32>,
+    /// How deeply functions may be nested in each other, the main chunk being at depth `0`
+    pub max_nesting_depth: Option<usize>,
+
+    /// Opcode numbering to decode instructions with, picked from the dump version if `None`
+    pub opcodes: Option<OpcodeTable>,
+
+    /// Keep decoding past damaged prototypes
+    ///
+    /// Unknown opcodes, bad constants or malformed debug info are recorded in
+    /// [`LuaJitPrototype::diagnostics`] instead of failing, and decoding carries on with the next
+    /// prototype, found through the size every prototype is prefixed with. An instruction that
+    /// can't be decoded is kept as a [`LuaJitOpcode::Unknown`] placeholder, so the ones after it
+    /// stay at their pc. Prototypes whose parent was too damaged to claim them are kept as well.
+    /// Exceeded limits and truncated input still fail.
+    ///
+    /// [`LuaJitPrototype::diagnostics`]: super::prototype::LuaJitPrototype::diagnostics
+    /// [`LuaJitOpcode::Unknown`]: super::opcodes::LuaJitOpcode::Unknown
+    pub lenient: bool,
+}
+
+impl DecodeOptions {
+    pub(crate) fn opcode_table(&self, header: &LuaJitHeader) -> &OpcodeTable {
+        self.opcodes
+            .as_ref()
+            .unwrap_or_else(|| LuaJitRevision::from(&header.version).opcode_table())
+    }
+
+    /// Turns the error of `result` into a diagnostic when decoding leniently
+    pub(crate) fn recover<T>(
+        &self,
+        result: Result<T>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Result<Option<T>> {
+        match result {
+            Ok(value) => Ok(Some(value)),
+            Err(error)
+                if self.lenient && !matches!(error.inner(), Error::LuaJitLimitExceeded { .. }) =>
+            {
+                diagnostics.push(error.into_diagnostic());
+                Ok(None)
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    pub(crate) fn check_file_size(&self, size: u64) -> Result<()> {
+        check(DecodeLimit::FileSize, size, self.max_file_size)
+    }
+
+    pub(crate) fn check_prototypes(&self, count: usize) -> Result<()> {
+        check(DecodeLimit::Prototypes, count, self.max_prototypes)
+    }
+
+    pub(crate) fn check_instructions(&self, count: u32) -> Result<()> {
+        check(DecodeLimit::Instructions, count, self.max_instructions)
+    }
+
+    pub(crate) fn check_constants(&self, count: u32) -> Result<()> {
+        check(DecodeLimit::Constants, count, self.max_constants)
+    }
+
+    pub(crate) fn check_string_length(&self, length: u32) -> Result<()> {
+        check(DecodeLimit::StringLength, length, self.max_string_length)
+    }
+
+    pub(crate) fn check_nesting_depth(&self, depth: usize) -> Result<()> {
+        check(DecodeLimit::NestingDepth, depth, self.max_nesting_depth)
+    }
+}
+
+fn check<T: TryInto<u64>>(limit: DecodeLimit, value: T, max: Option<T>) -> Result<()> {
+    let Some(max) = max else {
+        return Ok(());
+    };
+    // Neither side can be bigger than a `u64` on supported targets
+    let value = value.try_into().unwrap_or(u64::MAX);
+    let max = max.try_into().unwrap_or(u64::MAX);
+
+    if value > max {
+        Err(Error::LuaJitLimitExceeded { limit, value, max })
+    } else {
+        Ok(())
+    }
+}
@@ -1,14 +1,24 @@
 use super::{
-    constants::LuajitConstants,
-    debuginfo::DebugInformation,
+    constants::{ComplexConstantValue, LuajitConstants},
+    debuginfo::{DebugInformation, VariableInfo},
     header::{HeaderFlags, LuaJitHeader},
-    instruction::LuaJitInstruction,
-    read_uleb128, Error, Result,
+    instruction::{InstructionOperand, InstructionOperands, LuaJitInstruction},
+    labels::LabelTable,
+    opcodes::Opcode,
+    options::DecodeOptions,
+    read_uint, read_uleb128, write_uint, write_uleb128, DecodeSection, Error, Result,
+};
+use crate::decoder::{
+    error::Diagnostic,
+    lua_string::LuaString,
+    util::{read_u8, write_u8, Bounded, Endianness, Source},
 };
-use crate::decoder::util::{read_u8, Endianness};
 
 use bitflags::bitflags;
-use std::io::Read;
+use std::{
+    io::{ErrorKind, Read, Write},
+    ops::RangeInclusive,
+};
 
 bitflags! {
     #[derive(Clone, Debug, PartialEq, Eq, Hash)]
@@ -21,8 +31,13 @@ bitflags! {
     }
 }
 
+/// A single function of a dump
+///
+/// Bytecode positions (`pc`) follow LuaJIT's numbering, which `luajit -bl` prints as well: pc 0 is
+/// the implicit `FUNCF`/`FUNCV` header that isn't dumped, so the instruction at `pc` is
+/// `instructions()[pc - 1]`.
 #[derive(Clone, Debug, PartialEq)]
-pub struct LuaJitPrototype {
+pub struct LuaJitPrototype<'a> {
     flags: PrototypeFlags,
     arguments_count: u8,
     frame_size: u8,
@@ -31,31 +46,35 @@ pub struct LuaJitPrototype {
     lines_count: Option<u32>,
 
     instructions: Vec<LuaJitInstruction>,
-    constants: LuajitConstants,
-    debug_info: DebugInformation,
+    constants: LuajitConstants<'a>,
+    debug_info: Option<DebugInformation<'a>>,
+
+    /// Problems skipped over by a lenient decode
+    diagnostics: Vec<Diagnostic>,
 }
 
-enum ConstantType {
-    BcdumpKgcChild = 0,
-    BcdumpKgcTab = 1,
-    BcdumpKgcI64 = 2,
-    BcdumpKgcU64 = 3,
-    BcdumpKgcComplex = 4,
-    BcdumpKgcStr = 5,
+/// The fixed fields in front of every prototype body
+struct PrototypeHeader {
+    flags: PrototypeFlags,
+    arguments_count: u8,
+    frame_size: u8,
+    up_values_count: u8,
+
+    complex_constants_count: u32,
+    numeric_constants_count: u32,
+    instructions_count: u32,
+
+    debug_info_size: u32,
+    first_line_number: Option<u32>,
+    lines_count: Option<u32>,
 }
 
-impl LuaJitPrototype {
-    pub(crate) fn from_read<R: Read>(
+impl PrototypeHeader {
+    fn from_read<R: Read>(
         r: &mut R,
         header: &LuaJitHeader,
-        prototype_count: &mut u32,
-    ) -> Result<Option<Self>> {
-        let size = read_uleb128(r)?;
-
-        if size == 0 {
-            return Ok(None);
-        };
-
+        options: &DecodeOptions,
+    ) -> Result<Self> {
         let raw_flags = read_u8(r)?;
         let flags = PrototypeFlags::from_bits(raw_flags)
             .ok_or(Error::LuaJitInvalidPrototypeFlags(raw_flags.into()))?;
@@ -68,6 +87,9 @@ impl LuaJitPrototype {
         let numeric_constants_count = read_uleb128(r)?;
         let instructions_count = read_uleb128(r)?;
 
+        options.check_instructions(instructions_count)?;
+        options.check_constants(complex_constants_count.saturating_add(numeric_constants_count))?;
+
         let debug_info_size = if header.flags.contains(HeaderFlags::BCDUMP_F_STRIP) {
             0
         } else {
@@ -85,33 +107,123 @@ impl LuaJitPrototype {
             lines_count = None;
         }
 
+        let prototype_header = Self {
+            flags,
+            arguments_count,
+            frame_size,
+            up_values_count,
+            complex_constants_count,
+            numeric_constants_count,
+            instructions_count,
+            debug_info_size,
+            first_line_number,
+            lines_count,
+        };
+
+        Ok(prototype_header)
+    }
+}
+
+impl<'a> LuaJitPrototype<'a> {
+    /// `None` once the zero sized terminator is reached
+    pub(crate) fn from_read<S: Source<'a>>(
+        r: &mut S,
+        header: &LuaJitHeader,
+        child_stack: &mut Vec<u32>,
+        index: usize,
+        options: &DecodeOptions,
+    ) -> Result<Option<Self>> {
+        let size = read_uleb128(r).map_err(|e| e.in_section(DecodeSection::PrototypeHeader))?;
+
+        if size == 0 {
+            return Ok(None);
+        };
+
+        let mut r = Bounded::new(r, size.into());
+        let mut diagnostics = vec![];
+
+        let prototype_header = PrototypeHeader::from_read(&mut r, header, options)
+            .map_err(|e| e.in_section(DecodeSection::PrototypeHeader));
+        let Some(prototype_header) =
+            options.recover(locate(prototype_header, &r, index), &mut diagnostics)?
+        else {
+            // Nothing else can be trusted, but keeping a placeholder lets the parent still
+            // claim the right children
+            r.skip_rest()?;
+            return Ok(Some(Self::damaged(diagnostics)));
+        };
+
+        let PrototypeHeader {
+            flags,
+            arguments_count,
+            frame_size,
+            up_values_count,
+            complex_constants_count,
+            numeric_constants_count,
+            instructions_count,
+            debug_info_size,
+            first_line_number,
+            lines_count,
+        } = prototype_header;
+
+        let opcodes = options.opcode_table(header);
         let mut instructions = vec![];
-        for _ in 0..instructions_count {
-            let instruction = LuaJitInstruction::from_read(r, header, complex_constants_count)?;
+        let is_big_endian = header.flags.contains(HeaderFlags::BCDUMP_F_BE);
+        for pc in 1..=instructions_count {
+            let code_word = read_uint(&mut r, is_big_endian).map_err(|e| e.at_pc(pc));
+            let Some(code_word) =
+                options.recover(locate(code_word, &r, index), &mut diagnostics)?
+            else {
+                // The prototype ends early, so there is nothing left to decode
+                break;
+            };
+
+            let instruction =
+                LuaJitInstruction::decode(code_word, opcodes, pc).map_err(|e| e.at_pc(pc));
+            let instruction = options
+                .recover(locate(instruction, &r, index), &mut diagnostics)?
+                // Kept in place, so that the following instructions stay at their pc
+                .unwrap_or_else(|| LuaJitInstruction::unknown(code_word));
             instructions.push(instruction);
         }
 
-        let endianness = match header.flags.contains(HeaderFlags::BCDUMP_F_BE) {
-            true => Endianness::BigEndian,
-            false => Endianness::LittleEndian,
-        };
+        let endianness = Endianness::from(&header.flags);
         let constants = LuajitConstants::from_read(
-            r,
+            &mut r,
             up_values_count,
             complex_constants_count,
             numeric_constants_count,
             endianness,
-            prototype_count,
-        )?;
+            child_stack,
+            options,
+        )
+        .map_err(|e| e.in_section(DecodeSection::Constants));
+        let constants = options.recover(locate(constants, &r, index), &mut diagnostics)?;
 
-        let debug_info = DebugInformation::from_read(
-            r,
-            first_line_number.unwrap_or(0),
-            &header.flags,
-            instructions_count,
-            constants.up_value_references.len(),
-            &endianness,
-        )?;
+        let debug_info = match &constants {
+            Some(constants) if debug_info_size != 0 => {
+                let debug_info = DebugInformation::from_read(
+                    &mut r,
+                    first_line_number.unwrap_or(0),
+                    lines_count.unwrap_or(0),
+                    &header.flags,
+                    instructions_count,
+                    constants.up_value_references.len(),
+                    options,
+                )
+                .map_err(|e| e.in_section(DecodeSection::DebugInfo));
+                options.recover(locate(debug_info, &r, index), &mut diagnostics)?
+            }
+            _ => None,
+        };
+
+        // Left over bytes mean either the size or the contents are wrong, after damage they are
+        // just skipped
+        if diagnostics.is_empty() && r.remaining() != 0 {
+            let mismatch = Err(size_mismatch(&r, r.consumed()));
+            options.recover::<()>(locate(mismatch, &r, index), &mut diagnostics)?;
+        }
+        r.skip_rest()?;
 
         let prototype = Self {
             flags,
@@ -120,24 +232,321 @@ impl LuaJitPrototype {
             first_line_number,
             lines_count,
             instructions,
-            constants,
+            constants: constants.unwrap_or_default(),
             debug_info,
+            diagnostics,
         };
-        *prototype_count += 1;
 
         Ok(Some(prototype))
     }
-}
 
-impl From<u32> for ConstantType {
-    fn from(value: u32) -> Self {
-        match value {
-            0 => Self::BcdumpKgcChild,
-            1 => Self::BcdumpKgcTab,
-            2 => Self::BcdumpKgcI64,
-            3 => Self::BcdumpKgcU64,
-            4 => Self::BcdumpKgcComplex,
-            _ => Self::BcdumpKgcStr,
+    /// Stands in for a prototype whose header couldn't be decoded
+    fn damaged(diagnostics: Vec<Diagnostic>) -> Self {
+        Self {
+            flags: PrototypeFlags::empty(),
+            arguments_count: 0,
+            frame_size: 0,
+            first_line_number: None,
+            lines_count: None,
+            instructions: vec![],
+            constants: LuajitConstants::default(),
+            debug_info: None,
+            diagnostics,
+        }
+    }
+
+    /// Copies everything borrowed from the decoded input
+    pub fn into_owned(self) -> LuaJitPrototype<'static> {
+        LuaJitPrototype {
+            flags: self.flags,
+            arguments_count: self.arguments_count,
+            frame_size: self.frame_size,
+            first_line_number: self.first_line_number,
+            lines_count: self.lines_count,
+            instructions: self.instructions,
+            constants: self.constants.into_owned(),
+            debug_info: self.debug_info.map(DebugInformation::into_owned),
+            diagnostics: self.diagnostics,
+        }
+    }
+
+    pub(crate) fn write_to<W: Write>(&self, w: &mut W, header: &LuaJitHeader) -> Result<()> {
+        let endianness = Endianness::from(&header.flags);
+        let is_big_endian = header.flags.contains(HeaderFlags::BCDUMP_F_BE);
+
+        // The size prefix covers the whole body, so it is assembled up front
+        let mut body = vec![];
+
+        write_u8(&mut body, self.flags.bits())?;
+        write_u8(&mut body, self.arguments_count)?;
+        write_u8(&mut body, self.frame_size)?;
+        write_u8(&mut body, self.upvalues_count().try_into()?)?;
+
+        write_uleb128(
+            &mut body,
+            self.constants.complex_constants.len().try_into()?,
+        )?;
+        write_uleb128(
+            &mut body,
+            self.constants.numeric_constants.len().try_into()?,
+        )?;
+        write_uleb128(&mut body, self.instructions.len().try_into()?)?;
+
+        // Setting `BCDUMP_F_STRIP` on a decoded dump drops its debug info
+        let is_stripped = header.flags.contains(HeaderFlags::BCDUMP_F_STRIP);
+        let mut debug_info = vec![];
+        if let (Some(info), false) = (&self.debug_info, is_stripped) {
+            info.write_to(
+                &mut debug_info,
+                self.first_line_number.unwrap_or(0),
+                self.lines_count.unwrap_or(0),
+                &header.flags,
+            )?;
         }
+
+        if !is_stripped {
+            write_uleb128(&mut body, debug_info.len().try_into()?)?;
+
+            if !debug_info.is_empty() {
+                write_uleb128(&mut body, self.first_line_number.unwrap_or(0))?;
+                write_uleb128(&mut body, self.lines_count.unwrap_or(0))?;
+            }
+        }
+
+        for instruction in &self.instructions {
+            write_uint(&mut body, instruction.raw(), is_big_endian)?;
+        }
+
+        self.constants.write_to(&mut body, &endianness)?;
+        body.extend_from_slice(&debug_info);
+
+        write_uleb128(w, body.len().try_into()?)?;
+        w.write_all(&body)?;
+
+        Ok(())
+    }
+
+    pub fn flags(&self) -> &PrototypeFlags {
+        &self.flags
+    }
+
+    pub fn arguments_count(&self) -> u8 {
+        self.arguments_count
+    }
+
+    pub fn frame_size(&self) -> u8 {
+        self.frame_size
+    }
+
+    pub fn instructions(&self) -> &[LuaJitInstruction] {
+        &self.instructions
+    }
+
+    /// Absolute pc the instruction at `pc` may continue at instead of the next one
+    ///
+    /// Comparisons and tests have no target of their own, they branch to the one of the `JMP`
+    /// following them. `None` for instructions that never branch.
+    pub fn branch_target(&self, pc: usize) -> Option<u32> {
+        let instruction = self.instructions.get(pc.checked_sub(1)?)?;
+        if !instruction.opcode.canonical().is_comparison() {
+            return instruction.jump_target();
+        }
+
+        self.instructions
+            .get(pc)
+            .filter(|next| next.opcode.canonical() == Opcode::JMP)
+            .and_then(LuaJitInstruction::jump_target)
+    }
+
+    /// The jump targets, see [`LabelTable`]
+    pub fn labels(&self) -> LabelTable {
+        LabelTable::new(&self.instructions)
+    }
+
+    pub(crate) fn instructions_mut(&mut self) -> &mut [LuaJitInstruction] {
+        &mut self.instructions
+    }
+
+    pub fn constants(&self) -> &LuajitConstants<'a> {
+        &self.constants
+    }
+
+    /// Problems found in this prototype when decoding with [`DecodeOptions::lenient`]
+    ///
+    /// An instruction that couldn't be decoded is kept as a [`LuaJitOpcode::Unknown`] placeholder.
+    /// Anything else a problem affected is left out: the instructions missing from a prototype cut
+    /// short, the constants, the debug info, or everything when the prototype header is damaged.
+    ///
+    /// [`LuaJitOpcode::Unknown`]: super::opcodes::LuaJitOpcode::Unknown
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// `None` if the dump was stripped
+    pub fn debug_info(&self) -> Option<&DebugInformation<'a>> {
+        self.debug_info.as_ref()
+    }
+
+    pub fn upvalues_count(&self) -> usize {
+        self.constants.up_value_references.len()
+    }
+
+    pub fn is_variadic(&self) -> bool {
+        self.flags.contains(PrototypeFlags::FLAG_IS_VARIADIC)
+    }
+
+    pub fn has_ffi(&self) -> bool {
+        self.flags.contains(PrototypeFlags::FLAG_HAS_FFI)
+    }
+
+    pub fn has_iloop(&self) -> bool {
+        self.flags.contains(PrototypeFlags::FLAG_HAS_ILOOP)
+    }
+
+    /// Line where the function is defined, `0` for the main chunk
+    pub fn first_line(&self) -> Option<u32> {
+        self.first_line_number
+    }
+
+    pub fn lines_count(&self) -> Option<u32> {
+        self.lines_count
+    }
+
+    /// Source lines spanned by the function, as shown in `luajit -bl` headers
+    pub fn line_range(&self) -> Option<RangeInclusive<u64>> {
+        let first_line = u64::from(self.first_line_number?);
+        let lines_count = u64::from(self.lines_count?);
+        Some(first_line..=first_line + lines_count)
+    }
+
+    /// Source line of the instruction at `pc`
+    pub fn line_number(&self, pc: usize) -> Option<u64> {
+        let debug_info = self.debug_info.as_ref()?;
+        match pc.checked_sub(1) {
+            Some(index) => debug_info.line_numbers().get(index).copied(),
+            None => self.first_line_number.map(u64::from),
+        }
+    }
+
+    /// Pcs of the instructions generated for the source `line`, in order
+    ///
+    /// Only dumped instructions are looked at, pc `0`, the implicit function header, never shows
+    /// up. Nothing is found if the dump was stripped.
+    pub fn pcs_at_line(&self, line: u64) -> impl Iterator<Item = usize> + '_ {
+        let line_numbers = self
+            .debug_info
+            .as_ref()
+            .map_or(&[][..], DebugInformation::line_numbers);
+
+        line_numbers
+            .iter()
+            .enumerate()
+            .filter(move |&(_, &instruction_line)| instruction_line == line)
+            .map(|(index, _)| index + 1)
+    }
+
+    /// Names of the upvalues, empty if the dump was stripped
+    pub fn upvalue_names(&self) -> &[LuaString<'a>] {
+        self.debug_info
+            .as_ref()
+            .map_or(&[], DebugInformation::upvalue_names)
+    }
+
+    /// Local variables with the pc ranges they are live in, empty if the dump was stripped
+    pub fn variables(&self) -> &[VariableInfo<'a>] {
+        self.debug_info
+            .as_ref()
+            .map_or(&[], DebugInformation::variable_infos)
+    }
+
+    /// The local variable held in `slot` while the instruction at `pc` runs
+    ///
+    /// Slots are handed out the way LuaJIT names them in error messages: the variables live at
+    /// `pc` take slots from `0` up, in the order they were declared. Hidden ones such as the
+    /// `<index>` of a numeric `for` take a slot too. `None` for temporaries and if the dump was
+    /// stripped.
+    pub fn local_at(&self, slot: u16, pc: usize) -> Option<&VariableInfo<'a>> {
+        let pc = u32::try_from(pc).ok()?;
+
+        // Variables are listed by the pc they start at
+        self.variables()
+            .iter()
+            .take_while(|variable| variable.start_pc() <= pc)
+            .filter(|variable| variable.pc_range().contains(&pc))
+            .nth(slot.into())
+    }
+
+    /// Indices of the nested functions in [`DecodedLuaJitBytecode::prototypes`], in the order they
+    /// are defined in the source
+    ///
+    /// [`DecodedLuaJitBytecode::prototypes`]: super::DecodedLuaJitBytecode::prototypes
+    pub fn child_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        // `FNEW 0` refers to the last constant in the pool, which is the first defined child
+        self.constants
+            .complex_constants
+            .iter()
+            .rev()
+            .filter_map(|constant| match constant {
+                ComplexConstantValue::Child(index) => Some(*index as usize),
+                _ => None,
+            })
+    }
+
+    /// Index in [`DecodedLuaJitBytecode::prototypes`] of the function created by an `FNEW`
+    /// instruction
+    ///
+    /// [`DecodedLuaJitBytecode::prototypes`]: super::DecodedLuaJitBytecode::prototypes
+    pub fn fnew_child_index(&self, instruction: &LuaJitInstruction) -> Option<usize> {
+        match instruction.operands {
+            InstructionOperands::Ad {
+                d: Some(InstructionOperand::Function(d)),
+                ..
+            } => match self.constants.gc_constant(d) {
+                Some(ComplexConstantValue::Child(index)) => Some(*index as usize),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Resolves the cdata constant (`I64`, `U64` or `Complex`) loaded by a `KCDATA` instruction
+    pub fn cdata_constant(
+        &self,
+        instruction: &LuaJitInstruction,
+    ) -> Option<&ComplexConstantValue<'a>> {
+        match instruction.operands {
+            InstructionOperands::Ad {
+                d: Some(InstructionOperand::Cdata(d)),
+                ..
+            } => self
+                .constants
+                .gc_constant(d)
+                .filter(|constant| constant.is_cdata()),
+            _ => None,
+        }
+    }
+}
+
+/// Attaches where decoding stood to an error, so it is kept in diagnostics
+///
+/// Running out of input inside the prototype is blamed on its size when it happened at the end of
+/// the declared size rather than at the end of the input.
+fn locate<'a, T, S: Source<'a>>(result: Result<T>, r: &Bounded<S>, index: usize) -> Result<T> {
+    result.map_err(|e| {
+        let e = match (r.overrun(), e.inner()) {
+            (Some(consumed), Error::IoError(io)) if io.kind() == ErrorKind::UnexpectedEof => {
+                let mismatch = size_mismatch(r, consumed);
+                e.with_source(mismatch)
+            }
+            _ => e,
+        };
+        e.at_offset(r.position(), Some(index))
+    })
+}
+
+fn size_mismatch<S: Read>(r: &Bounded<S>, consumed: u64) -> Error {
+    Error::LuaJitPrototypeSizeMismatch {
+        declared: r.size(),
+        consumed,
     }
 }
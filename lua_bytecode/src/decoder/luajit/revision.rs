@@ -0,0 +1,322 @@
+//! Opcode numberings of the LuaJIT releases and forks
+
+use super::{
+    header::LuaJitVersion,
+    opcodes::{LuaJit20Opcode as Lj20, LuaJit21Opcode as Lj21, LuaJitOpcode, Opcode},
+    Error, Result,
+};
+
+use std::borrow::Cow;
+use LuaJitOpcode::{Lj20 as V20, Lj21 as V21};
+
+/// A LuaJIT release or fork with a known opcode numbering
+///
+/// The 2.1 table follows the `BCDEF` list of `src/lj_bc.h` in the rolling 2.1 branch of
+/// LuaJIT, checked at commit `a4f56a4` by decoding dumps its `luajit -b` wrote. 2.1.0-beta3 and
+/// OpenResty's `luajit2` share that list: `ISTYPE`, `ISNUM`, `TGETR` and `TSETR` all predate the
+/// beta3 tag, and OpenResty adds no opcodes of its own. The 2.0 table follows `src/lj_bc.h` of the
+/// `v2.0` branch.
+///
+/// RaptorJIT is deliberately left out, as its numbering hasn't been checked against dumps it
+/// wrote. Such dumps, or those of any other fork, can still be decoded by listing its `BCDEF` in
+/// [`OpcodeTable::from_names`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LuaJitRevision {
+    /// LuaJIT 2.0.x, writing dumps of version 1
+    LuaJit2_0,
+    /// LuaJIT 2.1.0-beta3
+    LuaJit2_1Beta3,
+    /// The rolling LuaJIT 2.1 releases, numbered like 2.1.0-beta3
+    LuaJit2_1,
+    /// OpenResty's LuaJIT 2.1 fork, which keeps the upstream numbering
+    OpenResty,
+}
+
+impl LuaJitRevision {
+    pub fn opcode_table(self) -> &'static OpcodeTable {
+        match self {
+            Self::LuaJit2_0 => &LUAJIT_2_0,
+            Self::LuaJit2_1Beta3 | Self::LuaJit2_1 | Self::OpenResty => &LUAJIT_2_1,
+        }
+    }
+}
+
+/// Revision a dump is decoded as unless [`DecodeOptions::opcodes`] says otherwise
+///
+/// [`DecodeOptions::opcodes`]: super::options::DecodeOptions::opcodes
+impl From<&LuaJitVersion> for LuaJitRevision {
+    fn from(version: &LuaJitVersion) -> Self {
+        match version {
+            LuaJitVersion::LuaJit2_0 => Self::LuaJit2_0,
+            LuaJitVersion::LuaJit2_1 => Self::LuaJit2_1,
+        }
+    }
+}
+
+/// Maps the opcode numbers found in a dump to opcodes
+///
+/// LuaJIT numbers its opcodes from `0` in the order of the `BCDEF` list of `lj_bc.h`. Forks that
+/// add, drop or reorder opcodes can be decoded by passing their own table in
+/// [`DecodeOptions::opcodes`].
+///
+/// [`DecodeOptions::opcodes`]: super::options::DecodeOptions::opcodes
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct OpcodeTable(Cow<'static, [LuaJitOpcode]>);
+
+impl OpcodeTable {
+    /// Numbers `opcodes` from `0` in the given order
+    pub fn new(opcodes: Vec<LuaJitOpcode>) -> Self {
+        Self(Cow::Owned(opcodes))
+    }
+
+    /// Numbers opcodes from `0` by their LuaJIT mnemonics, as listed in the `BCDEF` of a fork
+    ///
+    /// Mnemonics are looked up among the opcodes of `revision`.
+    pub fn from_names<'n>(
+        revision: LuaJitRevision,
+        names: impl IntoIterator<Item = &'n str>,
+    ) -> Result<Self> {
+        let known = revision.opcode_table();
+
+        let opcodes = names
+            .into_iter()
+            .map(|name| {
+                known
+                    .opcodes()
+                    .iter()
+                    .find(|opcode| opcode.to_string() == name)
+                    .cloned()
+                    .ok_or_else(|| Error::LuaJitUnknownOpcodeName(name.to_owned()))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self::new(opcodes))
+    }
+
+    /// The opcode numbered `number`, `None` if there is none
+    pub fn get(&self, number: u32) -> Option<&LuaJitOpcode> {
+        self.0.get(usize::try_from(number).ok()?)
+    }
+
+    /// Number of `opcode` in the table
+    pub fn number(&self, opcode: &LuaJitOpcode) -> Option<u32> {
+        let position = self.0.iter().position(|known| known == opcode)?;
+        position.try_into().ok()
+    }
+
+    /// Number of the opcode `opcode` is decoded as, whatever version the table is for
+    pub fn canonical_number(&self, opcode: Opcode) -> Option<u32> {
+        let position = self
+            .0
+            .iter()
+            .position(|known| known.canonical() == opcode)?;
+        position.try_into().ok()
+    }
+
+    /// Every opcode, in number order
+    pub fn opcodes(&self) -> &[LuaJitOpcode] {
+        &self.0
+    }
+
+    pub(crate) fn decode(&self, number: u32) -> Result<LuaJitOpcode> {
+        self.get(number)
+            .cloned()
+            .ok_or(Error::LuaJitInvalidOpcodeNumber(number))
+    }
+}
+
+static LUAJIT_2_0: OpcodeTable = OpcodeTable(Cow::Borrowed(&[
+    V20(Lj20::ISLT),
+    V20(Lj20::ISGE),
+    V20(Lj20::ISLE),
+    V20(Lj20::ISGT),
+    V20(Lj20::ISEQV),
+    V20(Lj20::ISNEV),
+    V20(Lj20::ISEQS),
+    V20(Lj20::ISNES),
+    V20(Lj20::ISEQN),
+    V20(Lj20::ISNEN),
+    V20(Lj20::ISEQP),
+    V20(Lj20::ISNEP),
+    V20(Lj20::ISTC),
+    V20(Lj20::ISFC),
+    V20(Lj20::IST),
+    V20(Lj20::ISF),
+    V20(Lj20::MOV),
+    V20(Lj20::NOT),
+    V20(Lj20::UNM),
+    V20(Lj20::LEN),
+    V20(Lj20::ADDVN),
+    V20(Lj20::SUBVN),
+    V20(Lj20::MULVN),
+    V20(Lj20::DIVVN),
+    V20(Lj20::MODVN),
+    V20(Lj20::ADDNV),
+    V20(Lj20::SUBNV),
+    V20(Lj20::MULNV),
+    V20(Lj20::DIVNV),
+    V20(Lj20::MODNV),
+    V20(Lj20::ADDVV),
+    V20(Lj20::SUBVV),
+    V20(Lj20::MULVV),
+    V20(Lj20::DIVVV),
+    V20(Lj20::MODVV),
+    V20(Lj20::POW),
+    V20(Lj20::CAT),
+    V20(Lj20::KSTR),
+    V20(Lj20::KCDATA),
+    V20(Lj20::KSHORT),
+    V20(Lj20::KNUM),
+    V20(Lj20::KPRI),
+    V20(Lj20::KNIL),
+    V20(Lj20::UGET),
+    V20(Lj20::USETV),
+    V20(Lj20::USETS),
+    V20(Lj20::USETN),
+    V20(Lj20::USETP),
+    V20(Lj20::UCLO),
+    V20(Lj20::FNEW),
+    V20(Lj20::TNEW),
+    V20(Lj20::TDUP),
+    V20(Lj20::GGET),
+    V20(Lj20::GSET),
+    V20(Lj20::TGETV),
+    V20(Lj20::TGETS),
+    V20(Lj20::TGETB),
+    V20(Lj20::TSETV),
+    V20(Lj20::TSETS),
+    V20(Lj20::TSETB),
+    V20(Lj20::TSETM),
+    V20(Lj20::CALLM),
+    V20(Lj20::CALL),
+    V20(Lj20::CALLMT),
+    V20(Lj20::CALLT),
+    V20(Lj20::ITERC),
+    V20(Lj20::ITERN),
+    V20(Lj20::VARG),
+    V20(Lj20::ISNEXT),
+    V20(Lj20::RETM),
+    V20(Lj20::RET),
+    V20(Lj20::RET0),
+    V20(Lj20::RET1),
+    V20(Lj20::FORI),
+    V20(Lj20::JFORI),
+    V20(Lj20::FORL),
+    V20(Lj20::IFORL),
+    V20(Lj20::JFORL),
+    V20(Lj20::ITERL),
+    V20(Lj20::IITERL),
+    V20(Lj20::JITERL),
+    V20(Lj20::LOOP),
+    V20(Lj20::ILOOP),
+    V20(Lj20::JLOOP),
+    V20(Lj20::JMP),
+    V20(Lj20::FUNCF),
+    V20(Lj20::IFUNCF),
+    V20(Lj20::JFUNCF),
+    V20(Lj20::FUNCV),
+    V20(Lj20::IFUNCV),
+    V20(Lj20::JFUNCV),
+    V20(Lj20::FUNCC),
+    V20(Lj20::FUNCCW),
+]));
+
+static LUAJIT_2_1: OpcodeTable = OpcodeTable(Cow::Borrowed(&[
+    V21(Lj21::ISLT),
+    V21(Lj21::ISGE),
+    V21(Lj21::ISLE),
+    V21(Lj21::ISGT),
+    V21(Lj21::ISEQV),
+    V21(Lj21::ISNEV),
+    V21(Lj21::ISEQS),
+    V21(Lj21::ISNES),
+    V21(Lj21::ISEQN),
+    V21(Lj21::ISNEN),
+    V21(Lj21::ISEQP),
+    V21(Lj21::ISNEP),
+    V21(Lj21::ISTC),
+    V21(Lj21::ISFC),
+    V21(Lj21::IST),
+    V21(Lj21::ISF),
+    V21(Lj21::ISTYPE),
+    V21(Lj21::ISNUM),
+    V21(Lj21::MOV),
+    V21(Lj21::NOT),
+    V21(Lj21::UNM),
+    V21(Lj21::LEN),
+    V21(Lj21::ADDVN),
+    V21(Lj21::SUBVN),
+    V21(Lj21::MULVN),
+    V21(Lj21::DIVVN),
+    V21(Lj21::MODVN),
+    V21(Lj21::ADDNV),
+    V21(Lj21::SUBNV),
+    V21(Lj21::MULNV),
+    V21(Lj21::DIVNV),
+    V21(Lj21::MODNV),
+    V21(Lj21::ADDVV),
+    V21(Lj21::SUBVV),
+    V21(Lj21::MULVV),
+    V21(Lj21::DIVVV),
+    V21(Lj21::MODVV),
+    V21(Lj21::POW),
+    V21(Lj21::CAT),
+    V21(Lj21::KSTR),
+    V21(Lj21::KCDATA),
+    V21(Lj21::KSHORT),
+    V21(Lj21::KNUM),
+    V21(Lj21::KPRI),
+    V21(Lj21::KNIL),
+    V21(Lj21::UGET),
+    V21(Lj21::USETV),
+    V21(Lj21::USETS),
+    V21(Lj21::USETN),
+    V21(Lj21::USETP),
+    V21(Lj21::UCLO),
+    V21(Lj21::FNEW),
+    V21(Lj21::TNEW),
+    V21(Lj21::TDUP),
+    V21(Lj21::GGET),
+    V21(Lj21::GSET),
+    V21(Lj21::TGETV),
+    V21(Lj21::TGETS),
+    V21(Lj21::TGETB),
+    V21(Lj21::TGETR),
+    V21(Lj21::TSETV),
+    V21(Lj21::TSETS),
+    V21(Lj21::TSETB),
+    V21(Lj21::TSETM),
+    V21(Lj21::TSETR),
+    V21(Lj21::CALLM),
+    V21(Lj21::CALL),
+    V21(Lj21::CALLMT),
+    V21(Lj21::CALLT),
+    V21(Lj21::ITERC),
+    V21(Lj21::ITERN),
+    V21(Lj21::VARG),
+    V21(Lj21::ISNEXT),
+    V21(Lj21::RETM),
+    V21(Lj21::RET),
+    V21(Lj21::RET0),
+    V21(Lj21::RET1),
+    V21(Lj21::FORI),
+    V21(Lj21::JFORI),
+    V21(Lj21::FORL),
+    V21(Lj21::IFORL),
+    V21(Lj21::JFORL),
+    V21(Lj21::ITERL),
+    V21(Lj21::IITERL),
+    V21(Lj21::JITERL),
+    V21(Lj21::LOOP),
+    V21(Lj21::ILOOP),
+    V21(Lj21::JLOOP),
+    V21(Lj21::JMP),
+    V21(Lj21::FUNCF),
+    V21(Lj21::IFUNCF),
+    V21(Lj21::JFUNCF),
+    V21(Lj21::FUNCV),
+    V21(Lj21::IFUNCV),
+    V21(Lj21::JFUNCV),
+    V21(Lj21::FUNCC),
+    V21(Lj21::FUNCCW),
+]));
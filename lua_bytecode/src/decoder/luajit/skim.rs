@@ -0,0 +1,119 @@
+//! Locating prototypes without decoding them
+
+use super::{
+    enforce_file_size, header::LuaJitHeader, options::DecodeOptions, read_uleb128, size_limited,
+    DecodeSection, Result,
+};
+use crate::decoder::util::{SliceReader, Source};
+
+use std::io::Read;
+
+/// Where a prototype is stored in a dump
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PrototypeLocation {
+    /// Offset of the prototype body, right after the size it is prefixed with
+    pub offset: u64,
+    /// Size of the body in bytes
+    pub size: u32,
+}
+
+impl PrototypeLocation {
+    /// Offset of the first byte after the body
+    pub fn end(&self) -> u64 {
+        self.offset + u64::from(self.size)
+    }
+}
+
+/// The prototypes of a dump, found by following the size of each one instead of decoding it
+///
+/// Much cheaper than a full decode on big files, but nothing inside the prototypes is looked at:
+/// the sizes are trusted and the nesting of functions is unknown. Prototypes are listed in dump
+/// order, the same as [`DecodedLuaJitBytecode::prototypes`].
+///
+/// [`DecodedLuaJitBytecode::prototypes`]: super::DecodedLuaJitBytecode::prototypes
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PrototypeIndex<'a> {
+    pub header: LuaJitHeader<'a>,
+    pub prototypes: Vec<PrototypeLocation>,
+}
+
+impl PrototypeIndex<'static> {
+    pub fn from_read<R: Read>(r: R) -> Result<Self> {
+        Self::from_read_with_options(r, &DecodeOptions::default())
+    }
+
+    /// Same as [`Self::from_read`], but fails with [`Error::LuaJitLimitExceeded`] once the dump
+    /// goes over the file size or prototype count of the `options`
+    ///
+    /// [`Error::LuaJitLimitExceeded`]: super::Error::LuaJitLimitExceeded
+    pub fn from_read_with_options<R: Read>(r: R, options: &DecodeOptions) -> Result<Self> {
+        let mut r = size_limited(r, options);
+        let index = Self::skim(&mut r, options);
+        enforce_file_size(index, &r, options)
+    }
+}
+
+impl<'a> PrototypeIndex<'a> {
+    pub fn from_slice(data: &'a [u8]) -> Result<Self> {
+        Self::from_slice_with_options(data, &DecodeOptions::default())
+    }
+
+    /// Same as [`Self::from_slice`], but fails with [`Error::LuaJitLimitExceeded`] once the dump
+    /// goes over the file size or prototype count of the `options`
+    ///
+    /// [`Error::LuaJitLimitExceeded`]: super::Error::LuaJitLimitExceeded
+    pub fn from_slice_with_options(data: &'a [u8], options: &DecodeOptions) -> Result<Self> {
+        options
+            .check_file_size(data.len().try_into()?)
+            .map_err(|e| e.at_offset(0, None))?;
+
+        Self::skim(&mut SliceReader::new(data), options)
+    }
+
+    fn skim<S: Source<'a>>(r: &mut S, options: &DecodeOptions) -> Result<Self> {
+        let header = LuaJitHeader::from_read(r, options).map_err(|e| {
+            e.in_section(DecodeSection::Header)
+                .at_offset(r.position(), None)
+        })?;
+
+        let mut prototypes = vec![];
+        loop {
+            let index = prototypes.len();
+            let location =
+                Self::skip_prototype(r).map_err(|e| e.at_offset(r.position(), Some(index)))?;
+
+            let Some(location) = location else {
+                break;
+            };
+
+            options
+                .check_prototypes(index + 1)
+                .map_err(|e| e.at_offset(r.position(), Some(index)))?;
+            prototypes.push(location);
+        }
+
+        Ok(Self { header, prototypes })
+    }
+
+    /// `None` once the zero sized terminator is reached
+    fn skip_prototype<S: Source<'a>>(r: &mut S) -> Result<Option<PrototypeLocation>> {
+        let size = read_uleb128(r).map_err(|e| e.in_section(DecodeSection::PrototypeHeader))?;
+
+        if size == 0 {
+            return Ok(None);
+        }
+
+        let offset = r.position();
+        r.skip(size.into())?;
+
+        Ok(Some(PrototypeLocation { offset, size }))
+    }
+
+    /// Copies everything borrowed from the input
+    pub fn into_owned(self) -> PrototypeIndex<'static> {
+        PrototypeIndex {
+            header: self.header.into_owned(),
+            prototypes: self.prototypes,
+        }
+    }
+}
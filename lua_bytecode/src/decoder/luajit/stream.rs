@@ -0,0 +1,89 @@
+//! Decoding prototypes one at a time
+
+use super::{
+    enforce_file_size, header::LuaJitHeader, options::DecodeOptions, prototype::LuaJitPrototype,
+    size_limited, DecodeSection, PrototypeDecoder, Result,
+};
+use crate::decoder::util::{CountingReader, Source};
+
+use std::io::{Read, Take};
+
+/// Reads the header of a dump up front, then yields its prototypes as they are decoded
+///
+/// Prototypes come in dump order, nested functions before the function defining them and the main
+/// chunk last, the same order as [`DecodedLuaJitBytecode::prototypes`]. Only a few bytes of
+/// bookkeeping are kept per prototype, so dropping each one after use keeps memory bounded and
+/// stopping early skips the rest of the input.
+///
+/// The iterator ends after the first error.
+///
+/// [`DecodedLuaJitBytecode::prototypes`]: super::DecodedLuaJitBytecode::prototypes
+pub struct PrototypeReader<R> {
+    r: CountingReader<Take<R>>,
+    header: LuaJitHeader<'static>,
+    options: DecodeOptions,
+    decoder: PrototypeDecoder,
+    finished: bool,
+}
+
+impl<R: Read> PrototypeReader<R> {
+    pub fn new(r: R) -> Result<Self> {
+        Self::with_options(r, DecodeOptions::default())
+    }
+
+    pub fn with_options(r: R, options: DecodeOptions) -> Result<Self> {
+        let mut r = size_limited(r, &options);
+
+        let header = LuaJitHeader::from_read(&mut r, &options).map_err(|e| {
+            e.in_section(DecodeSection::Header)
+                .at_offset(r.position(), None)
+        });
+        let header = enforce_file_size(header, &r, &options)?;
+
+        let reader = Self {
+            r,
+            header,
+            options,
+            decoder: PrototypeDecoder::default(),
+            finished: false,
+        };
+
+        Ok(reader)
+    }
+
+    pub fn header(&self) -> &LuaJitHeader<'static> {
+        &self.header
+    }
+
+    /// Bytes consumed from the input so far
+    pub fn position(&self) -> u64 {
+        self.r.position()
+    }
+}
+
+impl<R: Read> Iterator for PrototypeReader<R> {
+    type Item = Result<LuaJitPrototype<'static>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        let prototype = self.decoder.next(&mut self.r, &self.header, &self.options);
+        let prototype = enforce_file_size(prototype, &self.r, &self.options);
+
+        match prototype {
+            Ok(Some(prototype)) => Some(Ok(prototype)),
+            Ok(None) => {
+                self.finished = true;
+                None
+            }
+            Err(error) => {
+                self.finished = true;
+                Some(Err(error))
+            }
+        }
+    }
+}
+
+impl<R: Read> std::iter::FusedIterator for PrototypeReader<R> {}
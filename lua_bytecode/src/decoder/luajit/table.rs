@@ -1,7 +1,15 @@
-use super::Result;
+use super::{
+    constants::{join_halves, split_halves},
+    options::DecodeOptions,
+    write_uleb128, Error, Result,
+};
 
-use crate::decoder::{luajit::read_uleb128, util};
-use std::io::Read;
+use crate::decoder::{
+    lua_string::LuaString,
+    luajit::read_uleb128,
+    util::{self, Source},
+};
+use std::io::Write;
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum LuaJitTableItemTy {
@@ -13,17 +21,18 @@ pub enum LuaJitTableItemTy {
     BcdumpKtabStr = 5,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
-pub enum LuaJitTableItem {
+#[derive(Clone, Debug, PartialEq)]
+pub enum LuaJitTableItem<'a> {
     Nil,
     False,
     True,
-    Str(String),
+    Str(LuaString<'a>),
     Int(i32),
+    Num(f64),
 }
 
-impl LuaJitTableItem {
-    fn read_table_item<R: Read>(r: &mut R) -> Result<LuaJitTableItem> {
+impl<'a> LuaJitTableItem<'a> {
+    fn read_table_item<S: Source<'a>>(r: &mut S, options: &DecodeOptions) -> Result<Self> {
         let data_type_raw = read_uleb128(r)?;
         let data_type = LuaJitTableItemTy::from(data_type_raw);
 
@@ -32,9 +41,14 @@ impl LuaJitTableItem {
             LuaJitTableItemTy::BcdumpKtabFalse => LuaJitTableItem::False,
             LuaJitTableItemTy::BcdumpKtabTrue => LuaJitTableItem::True,
             LuaJitTableItemTy::BcdumpKtabInt => LuaJitTableItem::Int(read_uleb128(r)? as _),
-            LuaJitTableItemTy::BcdumpKtabNum => todo!(),
+            LuaJitTableItemTy::BcdumpKtabNum => {
+                let lo = read_uleb128(r)?;
+                let hi = read_uleb128(r)?;
+                LuaJitTableItem::Num(f64::from_bits(join_halves(lo, hi)))
+            }
             LuaJitTableItemTy::BcdumpKtabStr => {
                 let length = data_type_raw - 5;
+                options.check_string_length(length)?;
                 let string = util::read_string(r, length as usize)?;
                 LuaJitTableItem::Str(string)
             }
@@ -42,29 +56,92 @@ impl LuaJitTableItem {
 
         Ok(item)
     }
+
+    pub fn into_owned(self) -> LuaJitTableItem<'static> {
+        match self {
+            LuaJitTableItem::Nil => LuaJitTableItem::Nil,
+            LuaJitTableItem::False => LuaJitTableItem::False,
+            LuaJitTableItem::True => LuaJitTableItem::True,
+            LuaJitTableItem::Str(string) => LuaJitTableItem::Str(string.into_owned()),
+            LuaJitTableItem::Int(value) => LuaJitTableItem::Int(value),
+            LuaJitTableItem::Num(value) => LuaJitTableItem::Num(value),
+        }
+    }
+
+    fn write_to<W: Write>(&self, w: &mut W) -> Result<()> {
+        match self {
+            LuaJitTableItem::Nil => write_uleb128(w, LuaJitTableItemTy::BcdumpKtabNil as u32),
+            LuaJitTableItem::False => write_uleb128(w, LuaJitTableItemTy::BcdumpKtabFalse as u32),
+            LuaJitTableItem::True => write_uleb128(w, LuaJitTableItemTy::BcdumpKtabTrue as u32),
+            LuaJitTableItem::Int(value) => {
+                write_uleb128(w, LuaJitTableItemTy::BcdumpKtabInt as u32)?;
+                write_uleb128(w, *value as u32)
+            }
+            LuaJitTableItem::Num(value) => {
+                let (lo, hi) = split_halves(value.to_bits());
+                write_uleb128(w, LuaJitTableItemTy::BcdumpKtabNum as u32)?;
+                write_uleb128(w, lo)?;
+                write_uleb128(w, hi)
+            }
+            LuaJitTableItem::Str(string) => {
+                let length: u32 = string.len().try_into()?;
+                let data_type = length
+                    .checked_add(LuaJitTableItemTy::BcdumpKtabStr as u32)
+                    .ok_or(Error::ConvertError("usize", "table item type"))?;
+                write_uleb128(w, data_type)?;
+                w.write_all(string.as_bytes())?;
+                Ok(())
+            }
+        }
+    }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
-pub struct LuaJitTable {
-    array_items: Vec<LuaJitTableItem>,
-    hash_items: Vec<(LuaJitTableItem, LuaJitTableItem)>,
+#[derive(Clone, Debug, PartialEq)]
+pub struct LuaJitTable<'a> {
+    array_items: Vec<LuaJitTableItem<'a>>,
+    hash_items: Vec<(LuaJitTableItem<'a>, LuaJitTableItem<'a>)>,
 }
 
-impl LuaJitTable {
-    fn read_table<R: Read>(r: &mut R) -> Result<Self> {
+impl<'a> LuaJitTable<'a> {
+    pub fn array_items(&self) -> &[LuaJitTableItem<'a>] {
+        &self.array_items
+    }
+
+    pub fn hash_items(&self) -> &[(LuaJitTableItem<'a>, LuaJitTableItem<'a>)] {
+        &self.hash_items
+    }
+
+    pub fn into_owned(self) -> LuaJitTable<'static> {
+        LuaJitTable {
+            array_items: self
+                .array_items
+                .into_iter()
+                .map(LuaJitTableItem::into_owned)
+                .collect(),
+            hash_items: self
+                .hash_items
+                .into_iter()
+                .map(|(key, value)| (key.into_owned(), value.into_owned()))
+                .collect(),
+        }
+    }
+
+    pub(super) fn read_table<S: Source<'a>>(r: &mut S, options: &DecodeOptions) -> Result<Self> {
         let array_items_count = read_uleb128(r)?;
         let hash_items_count = read_uleb128(r)?;
 
-        let mut array_items = Vec::with_capacity(array_items_count as usize);
+        options.check_constants(array_items_count.saturating_add(hash_items_count))?;
+
+        let mut array_items = vec![];
         for _ in 0..array_items_count {
-            let constant = LuaJitTableItem::read_table_item(r)?;
+            let constant = LuaJitTableItem::read_table_item(r, options)?;
             array_items.push(constant);
         }
 
-        let mut hash_items = Vec::with_capacity(array_items_count as usize);
+        let mut hash_items = vec![];
         for _ in 0..hash_items_count {
-            let key = LuaJitTableItem::read_table_item(r)?;
-            let value = LuaJitTableItem::read_table_item(r)?;
+            let key = LuaJitTableItem::read_table_item(r, options)?;
+            let value = LuaJitTableItem::read_table_item(r, options)?;
             hash_items.push((key, value));
         }
 
@@ -75,6 +152,22 @@ impl LuaJitTable {
 
         Ok(table)
     }
+
+    pub(super) fn write_to<W: Write>(&self, w: &mut W) -> Result<()> {
+        write_uleb128(w, self.array_items.len().try_into()?)?;
+        write_uleb128(w, self.hash_items.len().try_into()?)?;
+
+        for item in &self.array_items {
+            item.write_to(w)?;
+        }
+
+        for (key, value) in &self.hash_items {
+            key.write_to(w)?;
+            value.write_to(w)?;
+        }
+
+        Ok(())
+    }
 }
 
 impl From<u32> for LuaJitTableItemTy {
@@ -1,4 +1,5 @@
 pub mod error;
+pub mod lua_string;
 
 pub mod luajit;
 mod util;
@@ -1,26 +1,315 @@
-use super::error::{Error, Result};
-use std::io::Read;
+use super::{
+    error::{Error, Result},
+    lua_string::LuaString,
+};
+use std::{
+    borrow::Cow,
+    io::{Read, Write},
+};
+
+/// Input the decoder reads from
+///
+/// Strings and other blocks of bytes are borrowed straight from the input when it is a slice, and
+/// copied out of it otherwise.
+pub(crate) trait Source<'a>: Read {
+    /// Bytes consumed so far
+    fn position(&self) -> u64;
+
+    fn read_block(&mut self, size: usize) -> Result<Cow<'a, [u8]>>;
+
+    /// Moves past `size` bytes without looking at them
+    fn skip(&mut self, size: u64) -> Result<()> {
+        let skipped = std::io::copy(&mut Read::take(&mut *self, size), &mut std::io::sink())?;
+
+        if skipped != size {
+            return Err(unexpected_eof());
+        }
+        Ok(())
+    }
+
+    /// Reads a zero terminated block whose first byte, `first`, was already consumed
+    ///
+    /// The terminator is consumed, but not part of the returned block. Gives `None` without
+    /// reading past `limit` more bytes if no terminator is found within them.
+    fn read_z_block_within(&mut self, first: u8, limit: u64) -> Result<Option<Cow<'a, [u8]>>>;
+
+    /// Reads a zero terminated block whose first byte, `first`, was already consumed
+    fn read_z_block_after(&mut self, first: u8) -> Result<Cow<'a, [u8]>> {
+        self.read_z_block_within(first, u64::MAX)?
+            .ok_or_else(unexpected_eof)
+    }
+}
+
+/// Keeps track of how many bytes were read, so errors can point at the offending offset
+pub(crate) struct CountingReader<R> {
+    inner: R,
+    position: u64,
+}
+
+impl<R: Read> CountingReader<R> {
+    pub(crate) fn new(inner: R) -> Self {
+        Self { inner, position: 0 }
+    }
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl<'a, R: Read> Source<'a> for CountingReader<R> {
+    fn position(&self) -> u64 {
+        self.position
+    }
+
+    fn read_block(&mut self, size: usize) -> Result<Cow<'a, [u8]>> {
+        Ok(Cow::Owned(load_block(self, size)?))
+    }
+
+    fn read_z_block_within(&mut self, first: u8, limit: u64) -> Result<Option<Cow<'a, [u8]>>> {
+        let mut buf = vec![first];
+        for _ in 0..limit {
+            match read_u8(self)? {
+                0 => return Ok(Some(Cow::Owned(buf))),
+                byte => buf.push(byte),
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Reads from a borrowed slice, handing out blocks that point into it
+pub(crate) struct SliceReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> SliceReader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    fn remaining(&self) -> &'a [u8] {
+        self.data.get(self.position..).unwrap_or_default()
+    }
+}
+
+impl Read for SliceReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.remaining().read(buf)?;
+        self.position += read;
+        Ok(read)
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> std::io::Result<()> {
+        self.remaining().read_exact(buf)?;
+        self.position += buf.len();
+        Ok(())
+    }
+}
+
+impl<'a> Source<'a> for SliceReader<'a> {
+    fn position(&self) -> u64 {
+        self.position as u64
+    }
+
+    fn read_block(&mut self, size: usize) -> Result<Cow<'a, [u8]>> {
+        let block = self.remaining().get(..size).ok_or_else(unexpected_eof)?;
+        self.position += size;
+        Ok(Cow::Borrowed(block))
+    }
+
+    fn skip(&mut self, size: u64) -> Result<()> {
+        let size = usize::try_from(size).map_err(|_| unexpected_eof())?;
+        if size > self.remaining().len() {
+            return Err(unexpected_eof());
+        }
+        self.position += size;
+        Ok(())
+    }
+
+    fn read_z_block_within(&mut self, _first: u8, limit: u64) -> Result<Option<Cow<'a, [u8]>>> {
+        // `first` is still in the slice, right before the current position
+        let start = self.position.checked_sub(1).ok_or_else(unexpected_eof)?;
+        let block = self.data.get(start..).unwrap_or_default();
+        let end = usize::try_from(limit)
+            .ok()
+            .and_then(|limit| limit.checked_add(1))
+            .filter(|&end| end < block.len());
+        let searched = end.map_or(block, |end| &block[..end]);
+
+        match searched.iter().position(|&byte| byte == 0) {
+            Some(length) => {
+                self.position = start + length + 1;
+                Ok(Some(Cow::Borrowed(&block[..length])))
+            }
+            // Like a reader, everything searched is consumed
+            None if end.is_some() => {
+                self.position = start + searched.len();
+                Ok(None)
+            }
+            None => Err(unexpected_eof()),
+        }
+    }
+}
+
+/// Restricts a source to the bytes of a single prototype
+pub(crate) struct Bounded<'s, S> {
+    inner: &'s mut S,
+    size: u64,
+    remaining: u64,
+    /// Bytes needed from the start of the region by the first read that didn't fit in it
+    overrun: Option<u64>,
+}
+
+impl<'s, S: Read> Bounded<'s, S> {
+    pub(crate) fn new(inner: &'s mut S, size: u64) -> Self {
+        Self {
+            inner,
+            size,
+            remaining: size,
+            overrun: None,
+        }
+    }
+
+    pub(crate) fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Bytes left before the end of the region
+    pub(crate) fn remaining(&self) -> u64 {
+        self.remaining
+    }
+
+    pub(crate) fn consumed(&self) -> u64 {
+        self.size - self.remaining
+    }
+
+    /// Set once something tried to read past the end of the region, to how far it wanted to go
+    pub(crate) fn overrun(&self) -> Option<u64> {
+        self.overrun
+    }
+
+    /// Fails, noting the overrun, if `size` more bytes don't fit in the region
+    fn reserve(&mut self, size: u64) -> Result<()> {
+        if size > self.remaining {
+            let needed = self.consumed().saturating_add(size);
+            self.overrun.get_or_insert(needed);
+            return Err(unexpected_eof());
+        }
+        Ok(())
+    }
+
+    fn consume(&mut self, size: u64) -> Result<()> {
+        match self.remaining.checked_sub(size) {
+            Some(remaining) => {
+                self.remaining = remaining;
+                Ok(())
+            }
+            None => {
+                self.remaining = 0;
+                Err(unexpected_eof())
+            }
+        }
+    }
+}
+
+impl<'a, S: Source<'a>> Bounded<'_, S> {
+    pub(crate) fn skip_rest(&mut self) -> Result<()> {
+        self.skip(self.remaining)
+    }
+}
+
+impl<S: Read> Read for Bounded<'_, S> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        // Short reads are fine, only those that can't be served at all run over the region
+        if self.remaining == 0 && !buf.is_empty() {
+            let needed = self.size.saturating_add(buf.len() as u64);
+            self.overrun.get_or_insert(needed);
+        }
+
+        let size = buf
+            .len()
+            .min(self.remaining.try_into().unwrap_or(usize::MAX));
+        let read = self.inner.read(&mut buf[..size])?;
+        self.remaining -= read as u64;
+        Ok(read)
+    }
+}
+
+impl<'a, S: Source<'a>> Source<'a> for Bounded<'_, S> {
+    fn position(&self) -> u64 {
+        self.inner.position()
+    }
+
+    fn read_block(&mut self, size: usize) -> Result<Cow<'a, [u8]>> {
+        self.reserve(size as u64)?;
+        let block = self.inner.read_block(size)?;
+        self.consume(size as u64)?;
+        Ok(block)
+    }
+
+    /// Skips `size` bytes, stopping at the end of the region
+    fn skip(&mut self, size: u64) -> Result<()> {
+        let size = size.min(self.remaining);
+        self.inner.skip(size)?;
+        self.consume(size)
+    }
+
+    fn read_z_block_within(&mut self, first: u8, limit: u64) -> Result<Option<Cow<'a, [u8]>>> {
+        // `first` was already taken off the bounded region, the terminator wasn't
+        let within = limit.min(self.remaining);
+        match self.inner.read_z_block_within(first, within)? {
+            Some(block) => {
+                self.consume(block.len() as u64)?;
+                Ok(Some(block))
+            }
+            None => {
+                self.consume(within)?;
+                if within < limit {
+                    // The terminator, if any, is past the end of the region
+                    self.reserve(1)?;
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
+fn unexpected_eof() -> Error {
+    std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into()
+}
 
 fn load_block<R: Read, T: Into<usize>>(r: &mut R, size: T) -> Result<Vec<u8>> {
     let size = size.into();
 
-    let mut buf = vec![0; size];
-    r.read_exact(&mut buf)?;
+    // The size comes from the input, so the buffer only grows as far as the data actually goes
+    let mut buf = vec![];
+    r.take(size as u64).read_to_end(&mut buf)?;
+
+    if buf.len() != size {
+        return Err(unexpected_eof());
+    }
 
     Ok(buf)
 }
 
-pub(crate) fn read_u8<R: Read>(r: &mut R) -> Result<u8> {
+pub(crate) fn read_u8<R: Read + ?Sized>(r: &mut R) -> Result<u8> {
     let mut buf = [0u8; 1];
     r.read_exact(&mut buf)?;
     Ok(buf[0])
 }
 
-pub(crate) fn read_string<R: Read, T: Into<usize>>(r: &mut R, size: T) -> Result<String> {
-    let size: usize = size.into();
-    let loaded_bytes = load_block(r, size)?;
-    let loaded_string = String::from_utf8(loaded_bytes)?;
-    Ok(loaded_string)
+pub(crate) fn write_u8<W: Write>(w: &mut W, value: u8) -> Result<()> {
+    w.write_all(&[value])?;
+    Ok(())
+}
+
+pub(crate) fn read_string<'a, S: Source<'a>>(r: &mut S, size: usize) -> Result<LuaString<'a>> {
+    Ok(r.read_block(size)?.into())
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -37,57 +326,46 @@ where
 {
     let int_size = int_size.into();
 
-    if int_size == 1 {
-        let byte: u64 = read_u8(r)?.into();
-        return Ok(byte);
-    }
-    let endianness: Endianness = endianness.into();
-    let loaded = load_block(r, int_size)?;
-
-    let uint32 = match endianness {
-        Endianness::BigEndian => match int_size {
-            2 => {
-                let loaded_as_array = loaded
-                    .try_into()
-                    .map_err(|_| Error::ConvertError("Vec<u8>", "[u8; 2]"))?;
-                u16::from_be_bytes(loaded_as_array).into()
-            }
-            4 => {
-                let loaded_as_array = loaded
-                    .try_into()
-                    .map_err(|_| Error::ConvertError("Vec<u8>", "[u8;4]"))?;
-                u32::from_be_bytes(loaded_as_array).into()
-            }
-            8 => {
-                let loaded_as_array = loaded
-                    .try_into()
-                    .map_err(|_| Error::ConvertError("Vec<u8>", "[u8;4]"))?;
-                u64::from_be_bytes(loaded_as_array)
-            }
-            _ => unreachable!(),
-        },
-        Endianness::LittleEndian => match int_size {
-            2 => {
-                let loaded_as_array = loaded
-                    .try_into()
-                    .map_err(|_| Error::ConvertError("Vec<u8>", "[u8; 2]"))?;
-                let val = u16::from_le_bytes(loaded_as_array);
-                val.into()
-            }
-            4 => {
-                let loaded_as_array = loaded
-                    .try_into()
-                    .map_err(|_| Error::ConvertError("Vec<u8>", "[u8;4]"))?;
-                u32::from_le_bytes(loaded_as_array).into()
-            }
-            8 => {
-                let loaded_as_array = loaded
-                    .try_into()
-                    .map_err(|_| Error::ConvertError("Vec<u8>", "[u8;4]"))?;
-                u64::from_le_bytes(loaded_as_array)
-            }
-            _ => unreachable!("{int_size}"),
-        },
+    let value = match (int_size, endianness.into()) {
+        (1, _) => read_u8(r)?.into(),
+        (2, Endianness::BigEndian) => u16::from_be_bytes(read_array(r)?).into(),
+        (2, Endianness::LittleEndian) => u16::from_le_bytes(read_array(r)?).into(),
+        (4, Endianness::BigEndian) => u32::from_be_bytes(read_array(r)?).into(),
+        (4, Endianness::LittleEndian) => u32::from_le_bytes(read_array(r)?).into(),
+        (8, Endianness::BigEndian) => u64::from_be_bytes(read_array(r)?),
+        (8, Endianness::LittleEndian) => u64::from_le_bytes(read_array(r)?),
+        _ => return Err(Error::ConvertError("bytes", "integer of unsupported size")),
     };
-    Ok(uint32)
+    Ok(value)
+}
+
+fn read_array<R: Read, const N: usize>(r: &mut R) -> Result<[u8; N]> {
+    let mut buf = [0u8; N];
+    r.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+pub(crate) fn write_varying_size_num<W, S, E>(
+    w: &mut W,
+    value: u64,
+    int_size: S,
+    endianness: E,
+) -> Result<()>
+where
+    W: Write,
+    S: Into<usize>,
+    E: Into<Endianness>,
+{
+    let int_size = int_size.into();
+
+    match (int_size, endianness.into()) {
+        (1, _) => write_u8(w, value.try_into()?),
+        (2, Endianness::BigEndian) => Ok(w.write_all(&u16::try_from(value)?.to_be_bytes())?),
+        (2, Endianness::LittleEndian) => Ok(w.write_all(&u16::try_from(value)?.to_le_bytes())?),
+        (4, Endianness::BigEndian) => Ok(w.write_all(&u32::try_from(value)?.to_be_bytes())?),
+        (4, Endianness::LittleEndian) => Ok(w.write_all(&u32::try_from(value)?.to_le_bytes())?),
+        (8, Endianness::BigEndian) => Ok(w.write_all(&value.to_be_bytes())?),
+        (8, Endianness::LittleEndian) => Ok(w.write_all(&value.to_le_bytes())?),
+        _ => Err(Error::ConvertError("u64", "integer of unsupported size")),
+    }
 }
@@ -0,0 +1,61 @@
+use lua_bytecode::decoder::{
+    lua_string::LuaString,
+    luajit::{constants::ComplexConstantValue, DecodedLuaJitBytecode},
+};
+use std::borrow::Cow;
+
+const SAMPLE: &[u8] = include_bytes!("../examples/files/compiled_1");
+
+/// Every string of the dump: chunk name, string constants, upvalue and variable names
+fn strings<'d, 'a>(decoded: &'d DecodedLuaJitBytecode<'a>) -> Vec<&'d LuaString<'a>> {
+    let mut strings: Vec<_> = decoded.header.chunk_name.iter().collect();
+    for prototype in &decoded.prototypes {
+        strings.extend(
+            prototype
+                .constants()
+                .complex_constants
+                .iter()
+                .filter_map(|constant| match constant {
+                    ComplexConstantValue::String(string) => Some(string),
+                    _ => None,
+                }),
+        );
+        strings.extend(prototype.upvalue_names());
+        strings.extend(prototype.variables().iter().map(|variable| variable.name()));
+    }
+    strings
+}
+
+#[test]
+fn from_slice_borrows() {
+    let decoded = DecodedLuaJitBytecode::from_slice(SAMPLE).unwrap();
+    let strings = strings(&decoded);
+    assert!(strings.iter().any(|string| string.as_bytes() == b"hello1"));
+    assert!(strings.iter().any(|string| string.as_bytes() == b"day"));
+
+    let input = SAMPLE.as_ptr_range();
+    for string in strings {
+        assert!(
+            input.contains(&string.as_bytes().as_ptr()),
+            "{string} is a copy"
+        );
+        assert!(matches!(string.clone().into_bytes(), Cow::Borrowed(_)));
+    }
+}
+
+#[test]
+fn into_owned_detaches() {
+    let mut buffer = SAMPLE.to_vec();
+    let owned = DecodedLuaJitBytecode::from_slice(&buffer)
+        .unwrap()
+        .into_owned();
+
+    // Neither changing nor freeing the input shows through
+    buffer.fill(0);
+    drop(buffer);
+
+    assert_eq!(owned, DecodedLuaJitBytecode::from_slice(SAMPLE).unwrap());
+    for string in strings(&owned) {
+        assert!(matches!(string.clone().into_bytes(), Cow::Owned(_)));
+    }
+}
@@ -0,0 +1,56 @@
+use lua_bytecode::decoder::luajit::{
+    instruction::CallSlots, opcodes::Opcode, DecodedLuaJitBytecode,
+};
+
+/// `(opcode, slots)` of every call and `VARG` of `calls.lua`
+fn call_slots(dump: &[u8]) -> Vec<(Opcode, CallSlots)> {
+    let decoded = DecodedLuaJitBytecode::from_slice(dump).unwrap();
+    decoded
+        .prototypes
+        .iter()
+        .flat_map(|prototype| prototype.instructions())
+        .filter_map(|instruction| {
+            let slots = instruction.call_slots(&decoded.header)?;
+            Some((instruction.opcode.canonical(), slots))
+        })
+        .collect()
+}
+
+#[test]
+fn varg_has_no_arguments() {
+    for dump in [
+        &include_bytes!("../examples/files/calls_fr2")[..],
+        &include_bytes!("../examples/files/calls_no_fr2")[..],
+    ] {
+        let vargs: Vec<_> = call_slots(dump)
+            .into_iter()
+            .filter(|(opcode, _)| *opcode == Opcode::VARG)
+            .collect();
+        assert!(!vargs.is_empty());
+
+        for (_, slots) in vargs {
+            assert_eq!(slots.function, None);
+            assert_eq!(slots.arguments, None);
+            assert!(!slots.multres_arguments);
+        }
+    }
+}
+
+#[test]
+fn fr2_moves_arguments_a_slot_further() {
+    let fr2 = call_slots(include_bytes!("../examples/files/calls_fr2"));
+    let no_fr2 = call_slots(include_bytes!("../examples/files/calls_no_fr2"));
+    assert_eq!(fr2.len(), no_fr2.len());
+
+    for ((opcode, fr2), (_, no_fr2)) in fr2.into_iter().zip(no_fr2) {
+        let (Some(function), Some(arguments)) = (fr2.function, fr2.arguments) else {
+            continue;
+        };
+        assert_eq!(arguments.start, function + 2, "{opcode}");
+
+        let (Some(function), Some(arguments)) = (no_fr2.function, no_fr2.arguments) else {
+            panic!("{opcode} has no arguments without FR2");
+        };
+        assert_eq!(arguments.start, function + 1, "{opcode}");
+    }
+}
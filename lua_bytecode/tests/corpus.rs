@@ -0,0 +1,45 @@
+use lua_bytecode::decoder::luajit::DecodedLuaJitBytecode;
+use std::{fs, panic, path::PathBuf};
+
+/// Every input of the `decode` fuzz target, seeds and regressions alike
+fn corpus() -> Vec<(PathBuf, Vec<u8>)> {
+    let directory = concat!(env!("CARGO_MANIFEST_DIR"), "/fuzz/corpus/decode");
+    let mut files: Vec<_> = fs::read_dir(directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.is_file())
+        .map(|path| {
+            let data = fs::read(&path).unwrap();
+            (path, data)
+        })
+        .collect();
+    files.sort();
+    assert!(!files.is_empty());
+    files
+}
+
+#[test]
+fn corpus_decodes_without_panicking() {
+    for (path, data) in corpus() {
+        let from_slice = panic::catch_unwind(|| DecodedLuaJitBytecode::from_slice(&data).is_ok());
+        let from_read = panic::catch_unwind(|| DecodedLuaJitBytecode::from_read(&data[..]).is_ok());
+
+        let (Ok(from_slice), Ok(from_read)) = (from_slice, from_read) else {
+            panic!("{} panicked while decoding", path.display());
+        };
+        assert_eq!(from_slice, from_read, "{}", path.display());
+    }
+}
+
+#[test]
+fn corpus_round_trips() {
+    for (path, data) in corpus() {
+        let Ok(decoded) = DecodedLuaJitBytecode::from_slice(&data) else {
+            continue;
+        };
+
+        let mut encoded = vec![];
+        decoded.write_to(&mut encoded).unwrap();
+        assert!(encoded == data, "{} re-encoded differently", path.display());
+    }
+}
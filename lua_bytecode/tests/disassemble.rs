@@ -0,0 +1,39 @@
+use lua_bytecode::decoder::luajit::DecodedLuaJitBytecode;
+
+fn disassemble(dump: &[u8]) -> String {
+    DecodedLuaJitBytecode::from_slice(dump)
+        .unwrap()
+        .disassemble()
+}
+
+#[test]
+fn sample_listing() {
+    assert_eq!(
+        disassemble(include_bytes!("../examples/files/compiled_1")),
+        include_str!("../examples/files/decoded.asm")
+    );
+}
+
+#[test]
+fn fr2_listing() {
+    assert_eq!(
+        disassemble(include_bytes!("../examples/files/calls_fr2")),
+        include_str!("../examples/files/calls_fr2.asm")
+    );
+}
+
+#[test]
+fn no_fr2_listing() {
+    assert_eq!(
+        disassemble(include_bytes!("../examples/files/calls_no_fr2")),
+        include_str!("../examples/files/calls_no_fr2.asm")
+    );
+}
+
+#[test]
+fn operands_listing() {
+    assert_eq!(
+        disassemble(include_bytes!("../examples/files/operands")),
+        include_str!("../examples/files/operands.asm")
+    );
+}
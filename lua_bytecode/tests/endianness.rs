@@ -0,0 +1,63 @@
+use lua_bytecode::decoder::luajit::{
+    constants::{ComplexConstantValue, LuaJitNumericConstant},
+    header::HeaderFlags,
+    table::LuaJitTableItem,
+    DecodedLuaJitBytecode,
+};
+
+const BIG_ENDIAN: &[u8] = include_bytes!("../examples/files/endian_be");
+
+#[test]
+fn big_endian_dump_decodes() {
+    let decoded = DecodedLuaJitBytecode::from_slice(BIG_ENDIAN).unwrap();
+    assert!(decoded.header.flags.contains(HeaderFlags::BCDUMP_F_BE));
+
+    let cdata: Vec<_> = decoded
+        .prototypes
+        .iter()
+        .flat_map(|prototype| &prototype.constants().complex_constants)
+        .filter(|constant| constant.is_cdata())
+        .collect();
+    assert!(cdata.contains(&&ComplexConstantValue::U64(0x123456789)));
+    assert!(cdata.contains(&&ComplexConstantValue::I64(-0x123456789)));
+    assert!(cdata.contains(&&ComplexConstantValue::Complex(0.0, 2.25)));
+
+    let table = decoded
+        .prototypes
+        .iter()
+        .flat_map(|prototype| &prototype.constants().complex_constants)
+        .find_map(|constant| match constant {
+            ComplexConstantValue::Table(table) => Some(table),
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(
+        &table.array_items()[1..4],
+        [
+            LuaJitTableItem::Num(0.1),
+            LuaJitTableItem::Num(2.5e300),
+            LuaJitTableItem::Num(-3.75)
+        ]
+    );
+
+    let numbers: Vec<_> = decoded
+        .prototypes
+        .iter()
+        .flat_map(|prototype| &prototype.constants().numeric_constants)
+        .collect();
+    assert!(numbers.contains(&&LuaJitNumericConstant::Number(1234567.891)));
+
+    let root = decoded.root().unwrap();
+    assert_eq!(root.line_range(), Some(0..=18));
+    assert_eq!(root.upvalues_count(), 0);
+    let bump = &decoded.prototypes[decoded.prototypes.len() - 2];
+    assert_eq!(bump.line_range(), Some(12..=15));
+}
+
+#[test]
+fn big_endian_dump_round_trips() {
+    let decoded = DecodedLuaJitBytecode::from_slice(BIG_ENDIAN).unwrap();
+    let mut encoded = vec![];
+    decoded.write_to(&mut encoded).unwrap();
+    assert_eq!(encoded, BIG_ENDIAN);
+}
@@ -0,0 +1,152 @@
+use lua_bytecode::decoder::{
+    error::{DecodeContext, DecodeSection, Error},
+    luajit::{skim::PrototypeIndex, DecodedLuaJitBytecode},
+};
+use std::io::ErrorKind;
+
+const SAMPLE: &[u8] = include_bytes!("../examples/files/compiled_1");
+const NAMES: &[u8] = include_bytes!("../examples/files/names");
+
+/// Offset of the instruction at `pc` of the prototype at `index`
+fn instruction_offset(index: usize, pc: usize) -> usize {
+    let decoded = DecodedLuaJitBytecode::from_slice(SAMPLE).unwrap();
+    let word = decoded.prototypes[index].instructions()[pc - 1].raw();
+    let location = &PrototypeIndex::from_slice(SAMPLE).unwrap().prototypes[index];
+
+    let body = location.offset as usize..location.end() as usize;
+    let offset = SAMPLE[body.clone()]
+        .windows(4)
+        .position(|window| window == word.to_le_bytes())
+        .unwrap();
+    body.start + offset
+}
+
+/// Decodes `dump` both from a slice and from a reader, which must fail the same way
+fn decode_error(dump: &[u8]) -> Error {
+    let from_read = DecodedLuaJitBytecode::from_read(dump).unwrap_err();
+    let from_slice = DecodedLuaJitBytecode::from_slice(dump).unwrap_err();
+    assert_eq!(from_read.context(), from_slice.context());
+    assert_eq!(from_read.to_string(), from_slice.to_string());
+    from_slice
+}
+
+fn is_eof(error: &Error) -> bool {
+    matches!(error.inner(), Error::IoError(io) if io.kind() == ErrorKind::UnexpectedEof)
+}
+
+#[test]
+fn truncated_header() {
+    let error = decode_error(&SAMPLE[..3]);
+
+    assert!(is_eof(&error));
+    assert_eq!(
+        error.context(),
+        Some(&DecodeContext {
+            offset: 3,
+            section: Some(DecodeSection::Header),
+            prototype: None,
+            pc: None,
+        })
+    );
+}
+
+#[test]
+fn truncated_instruction() {
+    // Cut in the middle of the second instruction of `add`
+    let cut = instruction_offset(0, 2) + 2;
+    let error = decode_error(&SAMPLE[..cut]);
+
+    assert!(is_eof(&error));
+    assert_eq!(
+        error.context(),
+        Some(&DecodeContext {
+            offset: cut as u64,
+            section: Some(DecodeSection::Instructions),
+            prototype: Some(0),
+            pc: Some(2),
+        })
+    );
+    assert_eq!(
+        error.to_string(),
+        format!(
+            "failed to fill whole buffer (at offset {cut:#x}, prototype 0, instructions, pc 2)"
+        )
+    );
+}
+
+#[test]
+fn truncated_constants() {
+    // Right after the last instruction of `divide`
+    let cut = instruction_offset(3, 8) + 4;
+    let error = decode_error(&SAMPLE[..cut]);
+
+    assert!(is_eof(&error));
+    let context = error.context().unwrap();
+    assert_eq!(context.section, Some(DecodeSection::Constants));
+    assert_eq!(context.prototype, Some(3));
+    assert_eq!(context.pc, None);
+}
+
+#[test]
+fn bad_opcode_word() {
+    let offset = instruction_offset(1, 1);
+    let mut damaged = SAMPLE.to_vec();
+    damaged[offset] = 0xfe;
+    let error = decode_error(&damaged);
+
+    assert!(matches!(
+        error.inner(),
+        Error::LuaJitInvalidOpcodeNumber(0xfe)
+    ));
+    // The word has been read in full when it turns out to be unknown
+    assert_eq!(
+        error.context(),
+        Some(&DecodeContext {
+            offset: offset as u64 + 4,
+            section: Some(DecodeSection::Instructions),
+            prototype: Some(1),
+            pc: Some(1),
+        })
+    );
+}
+
+/// `NAMES` with the size prefix of its first prototype changed by `delta`
+fn resize_first_prototype(delta: i8) -> (Vec<u8>, u64) {
+    let location = PrototypeIndex::from_slice(NAMES).unwrap().prototypes[0];
+    // Small enough for a single byte uleb128
+    let prefix = location.offset as usize - 1;
+    assert!(location.size < 0x80);
+
+    let mut dump = NAMES.to_vec();
+    dump[prefix] = dump[prefix].checked_add_signed(delta).unwrap();
+    (dump, location.size.into())
+}
+
+#[test]
+fn shrunk_prototype_size() {
+    // The prototype now ends in the middle of the upvalue name it finishes with
+    let (dump, size) = resize_first_prototype(-5);
+    let error = decode_error(&dump);
+
+    assert!(matches!(
+        error.inner(),
+        &Error::LuaJitPrototypeSizeMismatch { declared, consumed }
+            if declared == size - 5 && consumed == size - 4
+    ));
+    let context = error.context().unwrap();
+    assert_eq!(context.prototype, Some(0));
+    assert_eq!(context.section, Some(DecodeSection::DebugInfo));
+}
+
+#[test]
+fn grown_prototype_size() {
+    let (dump, size) = resize_first_prototype(2);
+    let error = decode_error(&dump);
+
+    assert!(matches!(
+        error.inner(),
+        &Error::LuaJitPrototypeSizeMismatch { declared, consumed }
+            if declared == size + 2 && consumed == size
+    ));
+    assert_eq!(error.context().unwrap().prototype, Some(0));
+}
@@ -0,0 +1,30 @@
+use lua_bytecode::decoder::luajit::{opcodes::Opcode, DecodedLuaJitBytecode};
+
+const SAMPLE: &[u8] = include_bytes!("../examples/files/compiled_1");
+
+#[test]
+fn day_type_labels() {
+    let decoded = DecodedLuaJitBytecode::from_slice(SAMPLE).unwrap();
+    // `function dayType(day)`, an `if` with six `elseif` branches
+    let day_type = &decoded.prototypes[4];
+    assert_eq!(day_type.first_line(), Some(28));
+
+    let labels = day_type.labels();
+    assert_eq!(labels.targets(), [6, 11, 16, 21, 26, 31, 36, 38]);
+    assert_eq!(labels.name(6).as_deref(), Some("L1"));
+    assert_eq!(labels.name(36).as_deref(), Some("L7"));
+    assert_eq!(labels.name(38).as_deref(), Some("L8"));
+    assert_eq!(labels.name(7), None);
+
+    // Each comparison branches through the `JMP` after it, every branch returns through `L8`
+    assert_eq!(day_type.branch_target(1), Some(6));
+    assert_eq!(day_type.branch_target(2), Some(6));
+    assert_eq!(day_type.branch_target(3), None);
+    for pc in [5, 10, 15, 20, 25, 30, 35] {
+        assert_eq!(
+            day_type.instructions()[pc - 1].opcode.canonical(),
+            Opcode::JMP
+        );
+        assert_eq!(day_type.branch_target(pc), Some(38));
+    }
+}
@@ -0,0 +1,71 @@
+use lua_bytecode::decoder::luajit::{
+    opcodes::LuaJitOpcode, options::DecodeOptions, DecodedLuaJitBytecode,
+};
+
+const SAMPLE: &[u8] = include_bytes!("../examples/files/compiled_1");
+
+/// Unknown opcode number written over the instruction at `pc` of the root prototype
+const PC: usize = 3;
+const OPCODE: u8 = 0xfe;
+
+fn damaged() -> Vec<u8> {
+    let decoded = DecodedLuaJitBytecode::from_slice(SAMPLE).unwrap();
+    let word = decoded.root().unwrap().instructions()[PC - 1].raw();
+
+    let offsets: Vec<_> = SAMPLE
+        .windows(4)
+        .enumerate()
+        .filter(|(_, window)| *window == word.to_le_bytes())
+        .map(|(offset, _)| offset)
+        .collect();
+    assert_eq!(offsets.len(), 1);
+
+    let mut damaged = SAMPLE.to_vec();
+    damaged[offsets[0]] = OPCODE;
+    damaged
+}
+
+#[test]
+fn unknown_opcode_fails_strict_decoding() {
+    assert!(DecodedLuaJitBytecode::from_slice(&damaged()).is_err());
+}
+
+#[test]
+fn unknown_opcode_keeps_following_instructions() {
+    let damaged = damaged();
+    let options = DecodeOptions {
+        lenient: true,
+        ..Default::default()
+    };
+    let decoded = DecodedLuaJitBytecode::from_slice_with_options(&damaged, &options).unwrap();
+    let original = DecodedLuaJitBytecode::from_slice(SAMPLE).unwrap();
+
+    let root = decoded.root().unwrap();
+    let original_root = original.root().unwrap();
+    assert_eq!(
+        root.instructions().len(),
+        original_root.instructions().len()
+    );
+    assert_eq!(
+        root.instructions()[PC - 1].opcode,
+        LuaJitOpcode::Unknown(OPCODE)
+    );
+    for (pc, (instruction, original)) in root
+        .instructions()
+        .iter()
+        .zip(original_root.instructions())
+        .enumerate()
+    {
+        if pc + 1 != PC {
+            assert_eq!(instruction, original, "pc {}", pc + 1);
+        }
+    }
+
+    let diagnostics: Vec<_> = decoded.diagnostics().collect();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].context.pc, Some(PC as u32));
+
+    let mut encoded = vec![];
+    decoded.write_to(&mut encoded).unwrap();
+    assert_eq!(encoded, damaged);
+}
@@ -0,0 +1,203 @@
+use lua_bytecode::decoder::{
+    error::{DecodeLimit, DecodeSection, Error},
+    luajit::{options::DecodeOptions, DecodedLuaJitBytecode},
+};
+
+const SAMPLE: &[u8] = include_bytes!("../examples/files/compiled_1");
+const NAMES: &[u8] = include_bytes!("../examples/files/names");
+const NESTED: &[u8] = include_bytes!("../examples/files/endian_be");
+
+/// Decodes `dump` both from a slice and from a reader, which must go over the same limit
+fn limit_error(dump: &[u8], options: &DecodeOptions) -> (DecodeLimit, u64, u64, Error) {
+    let from_read = DecodedLuaJitBytecode::from_read_with_options(dump, options).unwrap_err();
+    let from_slice = DecodedLuaJitBytecode::from_slice_with_options(dump, options).unwrap_err();
+    assert_eq!(from_read.to_string(), from_slice.to_string());
+
+    match *from_slice.inner() {
+        Error::LuaJitLimitExceeded { limit, value, max } => (limit, value, max, from_slice),
+        ref error => panic!("expected a limit error, got {error}"),
+    }
+}
+
+/// Decodes `dump` both from a slice and from a reader, which must stay within the limits
+fn decodes(dump: &[u8], options: &DecodeOptions) {
+    DecodedLuaJitBytecode::from_read_with_options(dump, options).unwrap();
+    DecodedLuaJitBytecode::from_slice_with_options(dump, options).unwrap();
+}
+
+#[test]
+fn file_size() {
+    let size = SAMPLE.len() as u64;
+    decodes(
+        SAMPLE,
+        &DecodeOptions {
+            max_file_size: Some(size),
+            ..Default::default()
+        },
+    );
+
+    let options = DecodeOptions {
+        max_file_size: Some(size - 1),
+        ..Default::default()
+    };
+    let from_slice = DecodedLuaJitBytecode::from_slice_with_options(SAMPLE, &options).unwrap_err();
+    assert!(matches!(
+        from_slice.inner(),
+        &Error::LuaJitLimitExceeded { limit: DecodeLimit::FileSize, value, max }
+            if value == size && max == size - 1
+    ));
+
+    // A reader has no length to check up front, so it stops once it has read too much
+    let from_read = DecodedLuaJitBytecode::from_read_with_options(SAMPLE, &options).unwrap_err();
+    assert!(matches!(
+        from_read.inner(),
+        &Error::LuaJitLimitExceeded { limit: DecodeLimit::FileSize, value, max }
+            if value > max && max == size - 1
+    ));
+}
+
+#[test]
+fn prototypes() {
+    let count = DecodedLuaJitBytecode::from_slice(SAMPLE)
+        .unwrap()
+        .prototypes
+        .len();
+    decodes(
+        SAMPLE,
+        &DecodeOptions {
+            max_prototypes: Some(count),
+            ..Default::default()
+        },
+    );
+
+    let options = DecodeOptions {
+        max_prototypes: Some(count - 1),
+        ..Default::default()
+    };
+    let (limit, value, max, _) = limit_error(SAMPLE, &options);
+    assert_eq!(
+        (limit, value, max),
+        (DecodeLimit::Prototypes, count as u64, count as u64 - 1)
+    );
+}
+
+#[test]
+fn instructions() {
+    let decoded = DecodedLuaJitBytecode::from_slice(SAMPLE).unwrap();
+    let most = decoded
+        .prototypes
+        .iter()
+        .map(|prototype| prototype.instructions().len() as u32)
+        .max()
+        .unwrap();
+    decodes(
+        SAMPLE,
+        &DecodeOptions {
+            max_instructions: Some(most),
+            ..Default::default()
+        },
+    );
+
+    let options = DecodeOptions {
+        max_instructions: Some(most - 1),
+        ..Default::default()
+    };
+    let (limit, value, max, error) = limit_error(SAMPLE, &options);
+    assert_eq!(
+        (limit, value, max),
+        (
+            DecodeLimit::Instructions,
+            u64::from(most),
+            u64::from(most) - 1
+        )
+    );
+
+    // Rejected from the counts, before any instruction of the prototype is read
+    let index = decoded
+        .prototypes
+        .iter()
+        .position(|prototype| prototype.instructions().len() as u32 == most)
+        .unwrap();
+    let context = error.context().unwrap();
+    assert_eq!(context.prototype, Some(index));
+    assert_eq!(context.section, Some(DecodeSection::PrototypeHeader));
+}
+
+#[test]
+fn constants() {
+    let decoded = DecodedLuaJitBytecode::from_slice(SAMPLE).unwrap();
+    let most = decoded
+        .prototypes
+        .iter()
+        .map(|prototype| {
+            let constants = prototype.constants();
+            (constants.complex_constants.len() + constants.numeric_constants.len()) as u32
+        })
+        .max()
+        .unwrap();
+    decodes(
+        SAMPLE,
+        &DecodeOptions {
+            max_constants: Some(most),
+            ..Default::default()
+        },
+    );
+
+    let options = DecodeOptions {
+        max_constants: Some(most - 1),
+        ..Default::default()
+    };
+    let (limit, value, max, _) = limit_error(SAMPLE, &options);
+    assert_eq!(
+        (limit, value, max),
+        (DecodeLimit::Constants, u64::from(most), u64::from(most) - 1)
+    );
+}
+
+#[test]
+fn string_length() {
+    // The upvalue name of the inner function is the longest string, 26 bytes
+    decodes(
+        NAMES,
+        &DecodeOptions {
+            max_string_length: Some(26),
+            ..Default::default()
+        },
+    );
+
+    let options = DecodeOptions {
+        max_string_length: Some(20),
+        ..Default::default()
+    };
+    let (limit, value, max, error) = limit_error(NAMES, &options);
+    assert_eq!((limit, value, max), (DecodeLimit::StringLength, 21, 20));
+
+    // Reading stops right after the 21st byte of the name, well before its terminator
+    let start = NAMES
+        .windows(8)
+        .position(|window| window == b"a_rather")
+        .unwrap() as u64;
+    let context = error.context().unwrap();
+    assert_eq!(context.offset, start + 21);
+    assert_eq!(context.prototype, Some(0));
+    assert_eq!(context.section, Some(DecodeSection::DebugInfo));
+}
+
+#[test]
+fn nesting_depth() {
+    // The main chunk holds a function that holds another one
+    decodes(
+        NESTED,
+        &DecodeOptions {
+            max_nesting_depth: Some(2),
+            ..Default::default()
+        },
+    );
+
+    let options = DecodeOptions {
+        max_nesting_depth: Some(1),
+        ..Default::default()
+    };
+    let (limit, value, max, _) = limit_error(NESTED, &options);
+    assert_eq!((limit, value, max), (DecodeLimit::NestingDepth, 2, 1));
+}
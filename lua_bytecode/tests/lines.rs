@@ -0,0 +1,50 @@
+use lua_bytecode::decoder::luajit::DecodedLuaJitBytecode;
+
+const SAMPLE: &[u8] = include_bytes!("../examples/files/compiled_1");
+const LISTING: &str = include_str!("../examples/files/decoded.asm");
+
+#[test]
+fn line_ranges_match_listing_headers() {
+    let decoded = DecodedLuaJitBytecode::from_slice(SAMPLE).unwrap();
+
+    let headers: Vec<_> = LISTING
+        .lines()
+        .filter_map(|line| line.strip_prefix("-- BYTECODE -- sample1.lua:"))
+        .map(|range| {
+            let (first, last) = range.split_once('-').unwrap();
+            first.parse::<u64>().unwrap()..=last.parse::<u64>().unwrap()
+        })
+        .collect();
+    let ranges: Vec<_> = decoded
+        .prototypes
+        .iter()
+        .map(|prototype| prototype.line_range().unwrap())
+        .collect();
+
+    assert_eq!(ranges, headers);
+}
+
+#[test]
+fn line_numbers_of_add() {
+    let decoded = DecodedLuaJitBytecode::from_slice(SAMPLE).unwrap();
+    let add = &decoded.prototypes[0];
+
+    // pc 0 is the function header, on the line of `function add(a, b)`
+    let lines: Vec<_> = (0..=8).map(|pc| add.line_number(pc).unwrap()).collect();
+    assert_eq!(lines, [2, 3, 3, 3, 4, 4, 4, 5, 5]);
+    assert_eq!(add.line_number(9), None);
+
+    assert_eq!(add.pcs_at_line(4).collect::<Vec<_>>(), [4, 5, 6]);
+    assert_eq!(add.pcs_at_line(5).collect::<Vec<_>>(), [7, 8]);
+    assert_eq!(add.pcs_at_line(2).count(), 0);
+}
+
+#[test]
+fn line_numbers_of_divide() {
+    let decoded = DecodedLuaJitBytecode::from_slice(SAMPLE).unwrap();
+    let divide = &decoded.prototypes[3];
+
+    assert_eq!(divide.line_number(1), Some(20));
+    assert_eq!(divide.line_number(3), Some(21));
+    assert_eq!(divide.line_number(6), Some(23));
+}
@@ -0,0 +1,30 @@
+use lua_bytecode::decoder::luajit::{
+    instruction::{InstructionOperand, InstructionOperands},
+    DecodedLuaJitBytecode,
+};
+
+const SAMPLE: &[u8] = include_bytes!("../examples/files/compiled_1");
+
+#[test]
+fn addvv_reads_parameters() {
+    let decoded = DecodedLuaJitBytecode::from_slice(SAMPLE).unwrap();
+    // `return a + b` of `function add(a, b)`
+    let add = &decoded.prototypes[0];
+    let pc = 7;
+
+    let instruction = &add.instructions()[pc - 1];
+    assert_eq!(instruction.opcode.to_string(), "ADDVV");
+    let InstructionOperands::Abc {
+        a: Some(InstructionOperand::Slot(a)),
+        b: Some(InstructionOperand::Slot(b)),
+        c: Some(InstructionOperand::Slot(c)),
+    } = instruction.operands
+    else {
+        panic!("ADDVV has three slot operands");
+    };
+
+    // The result goes to a temporary
+    assert!(add.local_at(a, pc).is_none());
+    assert_eq!(add.local_at(b, pc).unwrap().name().as_bytes(), b"a");
+    assert_eq!(add.local_at(c, pc).unwrap().name().as_bytes(), b"b");
+}
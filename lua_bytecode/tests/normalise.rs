@@ -0,0 +1,53 @@
+use lua_bytecode::decoder::luajit::{
+    normalise::UnnormalisedInstruction,
+    opcodes::{Opcode, OpcodeVariant},
+    DecodedLuaJitBytecode,
+};
+
+/// Read through `jit.util.funcbc` after the loops got hot, see `jit_patched.lua`
+const PATCHED: &[u8] = include_bytes!("../examples/files/jit_patched");
+
+fn opcodes(bytecode: &DecodedLuaJitBytecode) -> Vec<Opcode> {
+    bytecode.prototypes[0]
+        .instructions()
+        .iter()
+        .map(|instruction| instruction.opcode.canonical())
+        .collect()
+}
+
+#[test]
+fn patched_loops_get_their_base_opcodes_back() {
+    let mut bytecode = DecodedLuaJitBytecode::from_slice(PATCHED).unwrap();
+    let patched = opcodes(&bytecode);
+    assert_eq!(patched[6], Opcode::JFORL);
+    assert_eq!(patched[10], Opcode::JLOOP);
+
+    // The `JLOOP` of the `pairs` loop can't tell which `ITERL` it replaced
+    assert_eq!(
+        bytecode.normalise(),
+        [UnnormalisedInstruction {
+            prototype: 0,
+            pc: 25,
+            opcode: Opcode::JLOOP,
+        }]
+    );
+
+    let normalised = opcodes(&bytecode);
+    assert_eq!(normalised[6], Opcode::FORL);
+    assert_eq!(normalised[10], Opcode::LOOP);
+    for (index, opcode) in normalised.iter().enumerate() {
+        if index + 1 != 25 {
+            assert_eq!(opcode.variant(), OpcodeVariant::Base, "pc {}", index + 1);
+        }
+    }
+}
+
+#[test]
+fn normalising_twice_changes_nothing() {
+    let mut bytecode = DecodedLuaJitBytecode::from_slice(PATCHED).unwrap();
+    bytecode.normalise();
+    let once = bytecode.clone();
+
+    bytecode.normalise();
+    assert_eq!(bytecode, once);
+}
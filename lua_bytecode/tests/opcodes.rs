@@ -0,0 +1,39 @@
+use lua_bytecode::decoder::luajit::{
+    opcodes::{Opcode, OpcodeVariant},
+    revision::LuaJitRevision,
+};
+
+#[test]
+fn versions_share_canonical_opcodes() {
+    let lj20 = LuaJitRevision::LuaJit2_0.opcode_table();
+    let lj21 = LuaJitRevision::LuaJit2_1.opcode_table();
+
+    // 2.1 inserted `ISTYPE` and `ISNUM` after `ISF`, moving every later number
+    assert_eq!(lj20.canonical_number(Opcode::MOV), Some(16));
+    assert_eq!(lj21.canonical_number(Opcode::MOV), Some(18));
+    assert_eq!(lj20.canonical_number(Opcode::ISTYPE), None);
+
+    for opcode in lj20.opcodes() {
+        let canonical = opcode.canonical();
+        let number = lj21.canonical_number(canonical).unwrap();
+        assert_eq!(lj21.get(number).unwrap().canonical(), canonical);
+        assert_eq!(opcode.to_string(), canonical.to_string());
+    }
+}
+
+#[test]
+fn variants_map_to_their_base() {
+    assert_eq!(Opcode::IFORL.base(), Opcode::FORL);
+    assert_eq!(Opcode::JFORL.base(), Opcode::FORL);
+    assert_eq!(Opcode::JLOOP.base(), Opcode::LOOP);
+    assert_eq!(Opcode::FORL.base(), Opcode::FORL);
+
+    assert_eq!(Opcode::IFORL.variant(), OpcodeVariant::Interpreted);
+    assert_eq!(Opcode::JFORL.variant(), OpcodeVariant::Compiled);
+    assert_eq!(Opcode::FORL.variant(), OpcodeVariant::Base);
+
+    assert!(Opcode::JITERL.is_loop());
+    assert!(Opcode::CALLT.is_call() && Opcode::CALLT.is_return());
+    assert!(Opcode::ISEQS.is_comparison() && Opcode::ISEQS.is_branch());
+    assert!(!Opcode::LOOP.is_branch());
+}
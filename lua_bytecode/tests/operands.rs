@@ -0,0 +1,118 @@
+use lua_bytecode::decoder::{
+    error::Error,
+    luajit::{
+        constants::ComplexConstantValue, instruction::OperandValue, skim::PrototypeIndex,
+        DecodedLuaJitBytecode,
+    },
+};
+
+const OPERANDS: &[u8] = include_bytes!("../examples/files/operands");
+const SAMPLE: &[u8] = include_bytes!("../examples/files/compiled_1");
+
+#[test]
+fn kpri_resolves_to_primitives() {
+    let decoded = DecodedLuaJitBytecode::from_slice(OPERANDS).unwrap();
+    // `callback(nil, false, true)`
+    let flags = &decoded.prototypes[2];
+
+    let values: Vec<_> = flags.instructions()[1..4]
+        .iter()
+        .map(|instruction| {
+            assert_eq!(instruction.opcode.to_string(), "KPRI");
+            instruction.resolve_operand(flags).unwrap().unwrap()
+        })
+        .collect();
+    assert_eq!(
+        values,
+        [OperandValue::Nil, OperandValue::False, OperandValue::True]
+    );
+}
+
+#[test]
+fn literals_and_numbers_resolve() {
+    let decoded = DecodedLuaJitBytecode::from_slice(OPERANDS).unwrap();
+    let flags = &decoded.prototypes[2];
+    let instructions = flags.instructions();
+
+    assert_eq!(
+        instructions[5].resolve_operand(flags).unwrap(),
+        Some(OperandValue::Short(300))
+    );
+    let Some(OperandValue::Number(number)) = instructions[6].resolve_operand(flags).unwrap() else {
+        panic!("KNUM has no numeric constant");
+    };
+    assert_eq!(number.value(), 1.5);
+
+    // `MOV` has no constant to resolve
+    assert_eq!(instructions[0].resolve_operand(flags).unwrap(), None);
+}
+
+#[test]
+fn tsetm_resolves_to_start_index() {
+    let decoded = DecodedLuaJitBytecode::from_slice(OPERANDS).unwrap();
+
+    // `{ ... }` starts at 1, `{ "head", 2, ... }` after the two template items
+    for (prototype, start) in decoded.prototypes[..2].iter().zip([1, 3]) {
+        let tsetm = &prototype.instructions()[2];
+        assert_eq!(tsetm.opcode.to_string(), "TSETM");
+        assert_eq!(
+            tsetm.resolve_operand(prototype).unwrap(),
+            Some(OperandValue::TableIndex(start))
+        );
+    }
+}
+
+#[test]
+fn gc_constants_resolve_from_the_end() {
+    let decoded = DecodedLuaJitBytecode::from_slice(SAMPLE).unwrap();
+    let add = &decoded.prototypes[0];
+
+    // `GGET 2 0 ; "print"` and `KSTR 4 1 ; "hello1"`
+    let strings: Vec<_> = add.instructions()[..2]
+        .iter()
+        .map(
+            |instruction| match instruction.resolve_gc_constant(add).unwrap() {
+                Some(ComplexConstantValue::String(string)) => string.to_string_lossy().into_owned(),
+                constant => panic!("{constant:?} is no string"),
+            },
+        )
+        .collect();
+    assert_eq!(strings, ["print", "hello1"]);
+
+    // `FNEW 0 0`, the last GC constant of the main chunk is its first function
+    let root = decoded.root().unwrap();
+    assert_eq!(
+        root.instructions()[0].resolve_gc_constant(root).unwrap(),
+        Some(&ComplexConstantValue::Child(0))
+    );
+}
+
+#[test]
+fn gc_constant_of_the_wrong_kind() {
+    let decoded = DecodedLuaJitBytecode::from_slice(SAMPLE).unwrap();
+    let root = decoded.root().unwrap();
+    let fnew = root.instructions()[0].raw();
+    assert_eq!(root.instructions()[0].opcode.to_string(), "FNEW");
+
+    // Point `FNEW 0 0` at the GC constant `GSET 0 1 ; "add"` uses, a string instead of a function
+    let location =
+        PrototypeIndex::from_slice(SAMPLE).unwrap().prototypes[decoded.prototypes.len() - 1];
+    let body = location.offset as usize..location.end() as usize;
+    let offset = body.start
+        + SAMPLE[body]
+            .windows(4)
+            .position(|window| window == fnew.to_le_bytes())
+            .unwrap();
+    let mut dump = SAMPLE.to_vec();
+    dump[offset..offset + 4].copy_from_slice(&(fnew | 1 << 16).to_le_bytes());
+
+    let decoded = DecodedLuaJitBytecode::from_slice(&dump).unwrap();
+    let root = decoded.root().unwrap();
+    assert!(matches!(
+        root.instructions()[0].resolve_gc_constant(root),
+        Err(Error::LuaJitGcConstantKindMismatch {
+            index: 1,
+            expected: "function"
+        })
+    ));
+}
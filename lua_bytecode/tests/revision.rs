@@ -0,0 +1,54 @@
+use lua_bytecode::decoder::luajit::{
+    options::DecodeOptions,
+    revision::{LuaJitRevision, OpcodeTable},
+    DecodedLuaJitBytecode,
+};
+
+const SAMPLE: &[u8] = include_bytes!("../examples/files/compiled_1");
+
+/// Opcode of `return a + b` in `function add(a, b)`, decoded with `opcodes`
+fn add_opcode(opcodes: OpcodeTable) -> String {
+    let options = DecodeOptions {
+        opcodes: Some(opcodes),
+        ..Default::default()
+    };
+    let decoded = DecodedLuaJitBytecode::from_slice_with_options(SAMPLE, &options).unwrap();
+    decoded.prototypes[0].instructions()[6].opcode.to_string()
+}
+
+#[test]
+fn openresty_keeps_upstream_numbering() {
+    assert_eq!(
+        LuaJitRevision::OpenResty.opcode_table(),
+        LuaJitRevision::LuaJit2_1.opcode_table()
+    );
+    assert_eq!(
+        add_opcode(LuaJitRevision::OpenResty.opcode_table().clone()),
+        "ADDVV"
+    );
+}
+
+#[test]
+fn fork_numbering_from_names() {
+    // A made up fork of LuaJIT 2.1 that swapped `ADDVV` and `SUBVV`
+    let names: Vec<_> = LuaJitRevision::LuaJit2_1
+        .opcode_table()
+        .opcodes()
+        .iter()
+        .map(|opcode| match opcode.to_string().as_str() {
+            "ADDVV" => "SUBVV".to_owned(),
+            "SUBVV" => "ADDVV".to_owned(),
+            name => name.to_owned(),
+        })
+        .collect();
+    let fork = OpcodeTable::from_names(LuaJitRevision::LuaJit2_1, names.iter().map(String::as_str))
+        .unwrap();
+
+    assert_eq!(add_opcode(fork), "SUBVV");
+}
+
+#[test]
+fn unknown_names_are_rejected() {
+    let names = ["ISLT", "NOSUCHOP"];
+    assert!(OpcodeTable::from_names(LuaJitRevision::LuaJit2_1, names).is_err());
+}
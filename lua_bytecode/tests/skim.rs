@@ -0,0 +1,35 @@
+use lua_bytecode::decoder::luajit::{
+    skim::PrototypeIndex, stream::PrototypeReader, DecodedLuaJitBytecode,
+};
+
+const SAMPLE: &[u8] = include_bytes!("../examples/files/compiled_1");
+
+#[test]
+fn index_locates_every_prototype() {
+    let index = PrototypeIndex::from_slice(SAMPLE).unwrap();
+    let decoded = DecodedLuaJitBytecode::from_slice(SAMPLE).unwrap();
+
+    assert_eq!(index.header, decoded.header);
+    assert_eq!(index.prototypes.len(), decoded.prototypes.len());
+
+    // Prototypes follow each other, each behind its uleb128 size
+    for pair in index.prototypes.windows(2) {
+        assert!(pair[0].end() < pair[1].offset);
+    }
+    // The dump ends with the zero size closing the prototype list
+    assert_eq!(
+        index.prototypes.last().unwrap().end(),
+        SAMPLE.len() as u64 - 1
+    );
+}
+
+#[test]
+fn stream_position_matches_index() {
+    let index = PrototypeIndex::from_slice(SAMPLE).unwrap();
+    let mut reader = PrototypeReader::new(SAMPLE).unwrap();
+
+    for location in &index.prototypes {
+        reader.next().unwrap().unwrap();
+        assert_eq!(reader.position(), location.end());
+    }
+}
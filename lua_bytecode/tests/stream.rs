@@ -0,0 +1,41 @@
+use lua_bytecode::decoder::luajit::{
+    constants::ComplexConstantValue, stream::PrototypeReader, DecodedLuaJitBytecode,
+};
+
+const SAMPLE: &[u8] = include_bytes!("../examples/files/compiled_1");
+
+#[test]
+fn stream_yields_every_prototype() {
+    let reader = PrototypeReader::new(SAMPLE).unwrap();
+    let decoded = DecodedLuaJitBytecode::from_slice(SAMPLE).unwrap();
+    assert_eq!(reader.header(), &decoded.header);
+
+    let prototypes: Vec<_> = reader.map(Result::unwrap).collect();
+    assert_eq!(prototypes, decoded.prototypes);
+}
+
+#[test]
+fn stream_stops_at_the_prototype_looked_for() {
+    let mut reader = PrototypeReader::new(SAMPLE).unwrap();
+
+    let uses_monday = |constant: &ComplexConstantValue| matches!(constant, ComplexConstantValue::String(string) if string.as_bytes().starts_with(b"Monday"));
+    let found = reader
+        .by_ref()
+        .find(|prototype| {
+            prototype
+                .as_ref()
+                .unwrap()
+                .constants()
+                .complex_constants
+                .iter()
+                .any(uses_monday)
+        })
+        .unwrap()
+        .unwrap();
+    assert_eq!(found.line_range(), Some(28..=46));
+
+    // Only the function after it and the main chunk are left to read
+    let position = reader.position();
+    assert!(position < SAMPLE.len() as u64);
+    assert_eq!(reader.count(), 2);
+}
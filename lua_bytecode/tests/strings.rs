@@ -0,0 +1,33 @@
+use lua_bytecode::decoder::luajit::{constants::ComplexConstantValue, DecodedLuaJitBytecode};
+
+/// A string constant and a local name that aren't valid UTF-8
+const BINARY: &[u8] = include_bytes!("../examples/files/binary");
+
+#[test]
+fn binary_constant() {
+    let decoded = DecodedLuaJitBytecode::from_slice(BINARY).unwrap();
+    let ComplexConstantValue::String(string) =
+        &decoded.prototypes[0].constants().complex_constants[0]
+    else {
+        panic!("expected a string constant");
+    };
+
+    assert_eq!(string.as_bytes(), b"\xff\xfe tab\t\0 quote\" caf\xc3\xa9");
+    assert_eq!(string.to_str(), None);
+    assert_eq!(
+        string.escape().to_string(),
+        r#"\255\254 tab\t\000 quote\" café"#
+    );
+    assert_eq!(string.to_string(), "\u{fffd}\u{fffd} tab\t\0 quote\" café");
+}
+
+#[test]
+fn binary_name() {
+    let decoded = DecodedLuaJitBytecode::from_slice(BINARY).unwrap();
+    let name = decoded.prototypes[0].variables()[0].name();
+
+    assert_eq!(name.as_bytes(), b"caf\xe9");
+    assert_eq!(name.escape().to_string(), r"caf\233");
+    assert_eq!(name.to_string(), "caf\u{fffd}");
+    assert_eq!(format!("{name:?}"), r#""caf\233""#);
+}
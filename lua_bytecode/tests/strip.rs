@@ -0,0 +1,52 @@
+use lua_bytecode::decoder::luajit::{header::HeaderFlags, DecodedLuaJitBytecode};
+
+const SAMPLE: &[u8] = include_bytes!("../examples/files/compiled_1");
+/// `sample1.lua` dumped by the same LuaJIT build with `luajit -bs`
+const STRIPPED: &[u8] = include_bytes!("../examples/files/compiled_1_stripped");
+
+fn strip(dump: &[u8]) -> Vec<u8> {
+    let mut decoded = DecodedLuaJitBytecode::from_slice(dump).unwrap();
+    decoded.header.flags.insert(HeaderFlags::BCDUMP_F_STRIP);
+
+    let mut encoded = vec![];
+    decoded.write_to(&mut encoded).unwrap();
+    encoded
+}
+
+#[test]
+fn stripping_matches_luajit() {
+    assert_eq!(strip(SAMPLE), STRIPPED);
+}
+
+#[test]
+fn stripped_dump_decodes_again() {
+    let original = DecodedLuaJitBytecode::from_slice(SAMPLE).unwrap();
+    let encoded = strip(SAMPLE);
+    let stripped = DecodedLuaJitBytecode::from_slice(&encoded).unwrap();
+
+    assert!(stripped.header.flags.contains(HeaderFlags::BCDUMP_F_STRIP));
+    assert_eq!(stripped.header.chunk_name, None);
+    assert_eq!(stripped.prototypes.len(), original.prototypes.len());
+    for (prototype, original) in stripped.prototypes.iter().zip(&original.prototypes) {
+        assert!(prototype.debug_info().is_none());
+        assert_eq!(prototype.line_range(), None);
+        assert_eq!(prototype.instructions(), original.instructions());
+        assert_eq!(prototype.constants(), original.constants());
+    }
+
+    // Stripping twice changes nothing
+    assert_eq!(strip(&encoded), encoded);
+}
+
+#[test]
+fn unstripping_writes_an_empty_chunk_name() {
+    let mut decoded = DecodedLuaJitBytecode::from_slice(STRIPPED).unwrap();
+    decoded.header.flags.remove(HeaderFlags::BCDUMP_F_STRIP);
+
+    let mut encoded = vec![];
+    decoded.write_to(&mut encoded).unwrap();
+    let unstripped = DecodedLuaJitBytecode::from_slice(&encoded).unwrap();
+
+    assert_eq!(unstripped.header.chunk_name.map(|name| name.len()), Some(0));
+    assert_eq!(unstripped.prototypes, decoded.prototypes);
+}
@@ -0,0 +1,66 @@
+use lua_bytecode::decoder::luajit::{opcodes::Opcode, DecodedLuaJitBytecode};
+
+const SAMPLE: &[u8] = include_bytes!("../examples/files/compiled_1");
+
+#[test]
+fn sample_functions_are_children_of_the_root() {
+    let decoded = DecodedLuaJitBytecode::from_slice(SAMPLE).unwrap();
+    let root = decoded.prototypes.len() - 1;
+
+    // `add`, `subtract`, `multiply`, `divide`, `dayType` and the last function, in source order
+    let first_lines: Vec<_> = decoded
+        .children(root)
+        .map(|child| child.first_line().unwrap())
+        .collect();
+    assert_eq!(first_lines, [2, 9, 14, 19, 28, 49]);
+
+    for child in 0..root {
+        assert_eq!(decoded.parent(child), Some(root));
+        assert_eq!(decoded.children(child).count(), 0);
+    }
+    assert_eq!(decoded.parent(root), None);
+    assert_eq!(decoded.parent(root + 1), None);
+    assert_eq!(decoded.children(root + 1).count(), 0);
+}
+
+#[test]
+fn nested_closure_has_its_own_parent() {
+    let decoded =
+        DecodedLuaJitBytecode::from_slice(include_bytes!("../examples/files/endian_be")).unwrap();
+
+    // `bump` defines the closure it returns, the main chunk defines `scale` and `bump`
+    assert_eq!(decoded.parent(1), Some(2));
+    assert_eq!(decoded.parent(0), Some(3));
+    assert_eq!(decoded.parent(2), Some(3));
+    assert_eq!(
+        decoded.children(3).collect::<Vec<_>>(),
+        [&decoded.prototypes[0], &decoded.prototypes[2]]
+    );
+    assert_eq!(
+        decoded.children(2).collect::<Vec<_>>(),
+        [&decoded.prototypes[1]]
+    );
+}
+
+#[test]
+fn fnew_resolves_to_children_in_definition_order() {
+    let decoded = DecodedLuaJitBytecode::from_slice(SAMPLE).unwrap();
+    let root = decoded.root().unwrap();
+
+    let fnews: Vec<_> = root
+        .instructions()
+        .iter()
+        .filter(|instruction| instruction.opcode.canonical() == Opcode::FNEW)
+        .collect();
+
+    let children: Vec<_> = fnews
+        .iter()
+        .map(|fnew| root.fnew_child_index(fnew).unwrap())
+        .collect();
+    assert_eq!(children, root.child_indices().collect::<Vec<_>>());
+    assert_eq!(children, [0, 1, 2, 3, 4, 5]);
+
+    // `D = 0` is the first function defined
+    let first = decoded.resolve_fnew(root, fnews[0]).unwrap();
+    assert_eq!(first.first_line(), Some(2));
+}